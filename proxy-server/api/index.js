@@ -55,6 +55,40 @@ const generalRateLimit = createRateLimit(
   "General API rate limit exceeded. Please try again later."
 );
 
+// Conversation turns from the request. Clients send a `messages` array of
+// { role: "user" | "assistant", content } turns; older clients send a single
+// `prompt` string instead.
+const getTurns = ({ messages, prompt }) => {
+  if (Array.isArray(messages) && messages.length > 0) {
+    return messages.filter(
+      (m) =>
+        (m.role === "user" || m.role === "assistant") &&
+        typeof m.content === "string"
+    );
+  }
+  return prompt ? [{ role: "user", content: prompt }] : [];
+};
+
+// Report a failed upstream request inside an event stream, where the HTTP
+// status has already been sent. `status` is the upstream status, or 504/502
+// if the backend timed out or never answered, so clients can tell a rate
+// limit or outage from a bad request.
+const writeStreamError = (res, message, error) => {
+  const status =
+    error.response?.status || (error.code === "ECONNABORTED" ? 504 : 502);
+  if (!res.headersSent) {
+    res.setHeader("Content-Type", "text/event-stream");
+  }
+  res.write(
+    `data: ${JSON.stringify({
+      error: message,
+      status,
+      retryAfter: error.response?.headers?.["retry-after"],
+    })}\n\n`
+  );
+  res.end();
+};
+
 // Health check endpoint
 app.get("/health", (req, res) => {
   res.json({
@@ -69,15 +103,19 @@ app.post("/api/gemini", generalRateLimit, geminiRateLimit, async (req, res) => {
   try {
     const {
       model,
-      prompt,
       temperature = 0.7,
       maxTokens = 2048,
+      topP,
+      stopSequences,
+      seed,
+      system,
       stream = false,
     } = req.body;
+    const turns = getTurns(req.body);
 
-    if (!model || !prompt) {
+    if (!model || turns.length === 0) {
       return res.status(400).json({
-        error: "Missing required fields: model and prompt are required",
+        error: "Missing required fields: model and messages (or prompt) are required",
       });
     }
 
@@ -90,14 +128,17 @@ app.post("/api/gemini", generalRateLimit, geminiRateLimit, async (req, res) => {
     const url = `https://generativelanguage.googleapis.com/v1beta/models/${model}:streamGenerateContent?key=${process.env.GEMINI_API_KEY}`;
 
     const requestBody = {
-      contents: [
-        {
-          parts: [{ text: prompt }],
-        },
-      ],
+      ...(system && { systemInstruction: { parts: [{ text: system }] } }),
+      contents: turns.map((turn) => ({
+        role: turn.role === "assistant" ? "model" : "user",
+        parts: [{ text: turn.content }],
+      })),
       generationConfig: {
         temperature,
         maxOutputTokens: maxTokens,
+        topP,
+        stopSequences,
+        seed,
       },
     };
 
@@ -188,11 +229,8 @@ app.post("/api/gemini", generalRateLimit, geminiRateLimit, async (req, res) => {
   } catch (error) {
     console.error("Gemini API Error:", error.response?.data || error.message);
 
-    if (stream) {
-      res.write(
-        `data: ${JSON.stringify({ error: "Gemini API request failed" })}\n\n`
-      );
-      res.end();
+    if (req.body.stream) {
+      writeStreamError(res, "Gemini API request failed", error);
     } else {
       if (error.response?.status === 429) {
         res.status(429).json({
@@ -220,11 +258,19 @@ app.post(
   perplexityRateLimit,
   async (req, res) => {
     try {
-      const { model, prompt, stream = false } = req.body;
-
-      if (!model || !prompt) {
+      const {
+        model,
+        temperature,
+        topP,
+        maxTokens,
+        system,
+        stream = false,
+      } = req.body;
+      const turns = getTurns(req.body);
+
+      if (!model || turns.length === 0) {
         return res.status(400).json({
-          error: "Missing required fields: model and prompt are required",
+          error: "Missing required fields: model and messages (or prompt) are required",
         });
       }
 
@@ -241,14 +287,16 @@ app.post(
         messages: [
           {
             role: "system",
+            // A client-supplied system prompt replaces the default JSON answer format
             content:
+              system ||
               'You are a helpful AI assistant. You MUST respond with ONLY a valid JSON object. NO other text before or after the JSON.\n\nRequired JSON format:\n{\n  "summary": "Brief direct answer to the question",\n  "details": "Detailed explanation with proper markdown formatting. Use **bold** for emphasis, ```code blocks``` for code, and bullet points for lists.",\n  "key_points": ["Point 1", "Point 2", "Point 3"],\n  "status": "success"\n}\n\nCRITICAL RULES:\n- Return ONLY the JSON object, nothing else\n- Ensure all strings are properly quoted with double quotes\n- Use double quotes for all JSON keys and string values\n- NEVER include citation numbers like [1], [2], [3] in any field\n- Ensure all brackets and braces are properly closed\n- Test that your JSON is valid before responding\n- Keep summary concise and direct\n- Include 3-5 key points in the key_points array\n- Format currency as $XXX.XX (e.g., $517.93)\n- Format percentages as XX% (e.g., 1.86%)\n- Use proper spacing around numbers and text\n- Ensure mathematical symbols and formulas are clearly formatted',
           },
-          {
-            role: "user",
-            content: prompt,
-          },
+          ...turns,
         ],
+        temperature,
+        top_p: topP,
+        max_tokens: maxTokens,
         stream: stream,
       };
 
@@ -345,13 +393,8 @@ app.post(
         error.response?.data || error.message
       );
 
-      if (stream) {
-        res.write(
-          `data: ${JSON.stringify({
-            error: "Perplexity API request failed",
-          })}\n\n`
-        );
-        res.end();
+      if (req.body.stream) {
+        writeStreamError(res, "Perplexity API request failed", error);
       } else {
         if (error.response?.status === 429) {
           res.status(429).json({
@@ -377,17 +420,32 @@ app.post(
 app.get("/api/models", (req, res) => {
   res.json({
     gemini: [
+      {
+        id: "gemini-2.0-flash",
+        name: "Gemini 2.0 Flash",
+        provider: "Google",
+        description: "Advanced Gemini model for fast responses",
+        contextLength: 1048576,
+        vision: true,
+        tools: true,
+      },
       {
         id: "gemini-1.5-flash",
         name: "Gemini 1.5 Flash",
         provider: "Google",
         description: "Fast and efficient for quick responses",
+        contextLength: 1048576,
+        vision: true,
+        tools: true,
       },
       {
         id: "gemini-1.5-pro",
         name: "Gemini 1.5 Pro",
         provider: "Google",
         description: "Most capable model for complex tasks",
+        contextLength: 2097152,
+        vision: true,
+        tools: true,
       },
     ],
     perplexity: [
@@ -396,6 +454,9 @@ app.get("/api/models", (req, res) => {
         name: "Sonar",
         provider: "Perplexity",
         description: "Fast answers with reliable search results",
+        contextLength: 127072,
+        vision: false,
+        tools: false,
       },
     ],
   });
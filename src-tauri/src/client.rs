@@ -0,0 +1,110 @@
+// The HTTP client shared by every provider request, so connections are pooled
+// and the network settings (timeouts, proxy, extra CA certificates) apply
+// everywhere. It is rebuilt whenever those settings change.
+
+use crate::error::GhostError;
+use crate::providers::EventStream;
+use crate::settings::NetworkSettings;
+use futures_util::{stream, StreamExt};
+use reqwest::{Certificate, Client, Proxy};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The current client, kept in Tauri's managed state. Requests take a clone,
+/// which shares the connection pool, so swapping in a rebuilt client doesn't
+/// affect requests already under way.
+pub struct SharedClient(Mutex<Client>);
+
+impl SharedClient {
+    pub fn new(client: Client) -> Self {
+        Self(Mutex::new(client))
+    }
+
+    pub fn get(&self) -> Client {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, client: Client) {
+        *self.0.lock().unwrap() = client;
+    }
+}
+
+pub fn build(settings: &NetworkSettings) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_secs))
+        .read_timeout(Duration::from_secs(settings.idle_timeout_secs));
+
+    // An explicit proxy replaces the one from the HTTP(S)_PROXY environment variables
+    if let Some(url) = settings.proxy.as_deref().filter(|url| !url.trim().is_empty()) {
+        let proxy = Proxy::all(url).map_err(|e| format!("Invalid network proxy {}: {}", url, e))?;
+        builder = builder.proxy(proxy);
+    }
+
+    for path in &settings.ca_certificates {
+        for certificate in load_certificates(path)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create the HTTP client: {}", e))
+}
+
+// A PEM file may hold a whole bundle; anything else is taken as one DER certificate
+fn load_certificates(path: &Path) -> Result<Vec<Certificate>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read CA certificate {}: {}", path.display(), e))?;
+
+    let certificates = if bytes.windows(10).any(|w| w == b"-----BEGIN") {
+        Certificate::from_pem_bundle(&bytes)
+    } else {
+        Certificate::from_der(&bytes).map(|certificate| vec![certificate])
+    };
+    certificates.map_err(|e| format!("Invalid CA certificate {}: {}", path.display(), e))
+}
+
+/// Fail `events` with a timeout once the next event takes longer than
+/// `timeout`. Catches streams that stay connected, e.g. through keep-alives,
+/// but stop producing anything.
+pub fn with_stall_timeout(events: EventStream, timeout: Duration, service: String) -> EventStream {
+    let events = stream::unfold(Some(events), move |events| {
+        let service = service.clone();
+        async move {
+            let mut events = events?;
+            match tokio::time::timeout(timeout, events.next()).await {
+                Ok(Some(event)) => Some((event, Some(events))),
+                Ok(None) => None,
+                // Dropping the stream closes the connection
+                Err(_) => Some((Err(GhostError::Timeout { service }), None)),
+            }
+        }
+    });
+
+    Box::pin(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::StreamEvent;
+
+    #[tokio::test]
+    async fn stalled_stream_times_out() {
+        let stalled = stream::iter(vec![Ok(StreamEvent::Delta("Hi".to_string()))]).chain(stream::pending());
+        let events: Vec<_> = with_stall_timeout(Box::pin(stalled), Duration::from_millis(10), "gemini".to_string())
+            .collect()
+            .await;
+
+        assert_eq!(
+            events,
+            vec![
+                Ok(StreamEvent::Delta("Hi".to_string())),
+                Err(GhostError::Timeout {
+                    service: "gemini".to_string()
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// Token-aware context window. Tokens are estimated from text length since we
+// don't ship each provider's tokenizer; the estimate errs on the high side so
+// the real count stays under the budget.
+
+use crate::providers::{ChatMessage, Role};
+
+// Role markers and separators each provider adds around a turn
+const MESSAGE_OVERHEAD: usize = 4;
+
+pub struct TokenEstimator {
+    chars_per_token: f32,
+}
+
+impl TokenEstimator {
+    pub fn for_model(model: &str) -> Self {
+        let chars_per_token = if model.starts_with("gemini") {
+            4.0
+        } else {
+            // Llama-family tokenizers (e.g. Perplexity Sonar) are a little less dense
+            3.5
+        };
+        Self { chars_per_token }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        // Non-ASCII text (CJK, emoji, accents) is close to one token per character
+        let (ascii, other) = text.chars().fold((0, 0), |(ascii, other), c| {
+            if c.is_ascii() {
+                (ascii + 1, other)
+            } else {
+                (ascii, other + 1)
+            }
+        });
+        (ascii as f32 / self.chars_per_token).ceil() as usize + other
+    }
+
+    pub fn message_tokens(&self, message: &ChatMessage) -> usize {
+        self.count(&message.content) + MESSAGE_OVERHEAD
+    }
+}
+
+/// Context length assumed for models whose provider doesn't report one.
+pub const DEFAULT_CONTEXT_WINDOW: usize = 32_768;
+
+/// Keep the newest turns that fit in `budget` tokens, dropping the oldest
+/// first. The newest turn is always kept, even if it alone is over budget.
+pub fn fit_to_budget(turns: Vec<ChatMessage>, estimator: &TokenEstimator, budget: usize) -> Vec<ChatMessage> {
+    let mut used = 0;
+    let mut keep = 0;
+
+    for turn in turns.iter().rev() {
+        let cost = estimator.message_tokens(turn);
+        if keep > 0 && used + cost > budget {
+            break;
+        }
+        used += cost;
+        keep += 1;
+    }
+
+    let skip = turns.len() - keep;
+    let mut fitted: Vec<ChatMessage> = turns.into_iter().skip(skip).collect();
+
+    // Providers expect the context to open with a user turn
+    if fitted.len() > 1 && fitted[0].role == Role::Assistant {
+        fitted.remove(0);
+    }
+
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(role: Role, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn estimates_ascii_and_non_ascii_text() {
+        let estimator = TokenEstimator::for_model("gemini-2.0-flash");
+        assert_eq!(estimator.count(""), 0);
+        assert_eq!(estimator.count("abcdefgh"), 2);
+        assert_eq!(estimator.count("abcde"), 2);
+        assert_eq!(estimator.count("世界"), 2);
+    }
+
+    #[test]
+    fn keeps_everything_within_budget() {
+        let turns = vec![turn(Role::User, "hi"), turn(Role::Assistant, "hello"), turn(Role::User, "bye")];
+        let estimator = TokenEstimator::for_model("gemini-2.0-flash");
+        assert_eq!(fit_to_budget(turns, &estimator, 1000).len(), 3);
+    }
+
+    #[test]
+    fn drops_oldest_turns_first_and_starts_with_user() {
+        let long = "x".repeat(400); // 100 tokens + overhead
+        let turns = vec![
+            turn(Role::User, &long),
+            turn(Role::Assistant, &long),
+            turn(Role::User, &long),
+            turn(Role::Assistant, &long),
+            turn(Role::User, "latest"),
+        ];
+        let estimator = TokenEstimator::for_model("gemini-2.0-flash");
+
+        // Room for the latest turn plus one long one; the assistant turn that
+        // would open the context is dropped too
+        let fitted = fit_to_budget(turns, &estimator, 150);
+        assert_eq!(fitted.len(), 1);
+        assert_eq!(fitted[0].content, "latest");
+
+        let turns = vec![turn(Role::User, &long), turn(Role::Assistant, &long), turn(Role::User, "latest")];
+        let fitted = fit_to_budget(turns, &estimator, 300);
+        assert_eq!(fitted.len(), 3);
+    }
+
+    #[test]
+    fn newest_turn_is_kept_even_if_over_budget() {
+        let turns = vec![turn(Role::User, "old"), turn(Role::User, &"x".repeat(4000))];
+        let estimator = TokenEstimator::for_model("gemini-2.0-flash");
+        let fitted = fit_to_budget(turns, &estimator, 10);
+        assert_eq!(fitted.len(), 1);
+        assert_eq!(fitted[0].content.len(), 4000);
+    }
+}
@@ -0,0 +1,471 @@
+use crate::context::{self, TokenEstimator};
+use crate::error::GhostError;
+use crate::providers::{ChatMessage, Role};
+use crate::settings::ContextSettings;
+use crate::store::ConversationStore;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationMessage {
+    pub id: String,
+    pub role: String, // "user" or "assistant"
+    pub content: String,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub truncated: bool, // true if generation was stopped before the model finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>, // model that wrote an assistant message, which may be a fallback
+}
+
+/// Running summary of turns that no longer fit in the context window.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Summary {
+    pub text: String,
+    /// How many messages, counted from the start of the conversation, the summary covers.
+    pub covered: usize,
+}
+
+/// Messages waiting to be folded into the summary.
+#[derive(Debug)]
+pub struct SummaryJob {
+    pub previous: Option<String>,
+    pub messages: Vec<ConversationMessage>,
+    covered: usize,
+    epoch: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Conversation {
+    messages: VecDeque<ConversationMessage>,
+    max_messages: usize,
+    summary: Option<Summary>,
+    // Whether messages pushed out of memory are held for the summary
+    summarize: bool,
+    // Messages pushed out of memory before the summary caught up with them,
+    // at most `max_messages` of them
+    evicted: Vec<ConversationMessage>,
+    // Total messages pushed out of memory; the first in-memory message has this index
+    evicted_count: usize,
+    summarizing: bool,
+    // Bumped on clear so a summary started before clearing is discarded
+    epoch: u64,
+    #[serde(skip)]
+    store: Option<ConversationStore>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            max_messages: 200, // Upper bound on history kept in memory; the token budget decides what is sent
+            summary: None,
+            summarize: true,
+            evicted: Vec::new(),
+            evicted_count: 0,
+            summarizing: false,
+            epoch: 0,
+            store: None,
+        }
+    }
+
+    /// Conversation backed by `store`, with the most recent stored messages
+    /// loaded as context.
+    pub fn with_store(store: ConversationStore, max_messages: usize, summarize: bool) -> Self {
+        let mut conversation = Self::new();
+        conversation.max_messages = max_messages;
+        conversation.summarize = summarize;
+
+        match store.load_summary() {
+            Ok(summary) => conversation.summary = summary,
+            Err(e) => log::error!("Failed to load conversation summary: {}", e),
+        }
+
+        match store.load() {
+            Ok(messages) => {
+                for message in messages {
+                    conversation.push(message);
+                }
+            }
+            Err(e) => log::error!("Failed to load conversation history: {}", e),
+        }
+
+        conversation.store = Some(store);
+        conversation
+    }
+
+    pub fn add_message(&mut self, role: String, content: String) -> Result<String, GhostError> {
+        self.push_message(role, content, false, None)
+    }
+
+    // Record the assistant's answer and the model that wrote it. `truncated`
+    // marks an answer that was cut short, by the user or the token limit.
+    pub fn add_answer(&mut self, content: String, model: String, truncated: bool) -> Result<String, GhostError> {
+        self.push_message("assistant".to_string(), content, truncated, Some(model))
+    }
+
+    // A message that can't be saved isn't kept in memory either, so the
+    // context never holds turns that will be gone after a restart
+    fn push_message(
+        &mut self,
+        role: String,
+        content: String,
+        truncated: bool,
+        model: Option<String>,
+    ) -> Result<String, GhostError> {
+        let id = Uuid::new_v4().to_string();
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let message = ConversationMessage {
+            id: id.clone(),
+            role,
+            content,
+            timestamp,
+            truncated,
+            model,
+        };
+
+        if let Some(store) = &self.store {
+            store
+                .append(&message)
+                .map_err(|e| GhostError::Storage(format!("Failed to save message: {}", e)))?;
+        }
+
+        self.push(message);
+        Ok(id)
+    }
+
+    /// Keep at most `max_messages` in memory. Unless `summarize` is set, the
+    /// ones pushed out are dropped rather than held for the summary.
+    pub fn set_limits(&mut self, max_messages: usize, summarize: bool) {
+        self.max_messages = max_messages;
+        self.summarize = summarize;
+        if !summarize {
+            self.evicted.clear();
+        }
+        self.evict();
+    }
+
+    fn push(&mut self, message: ConversationMessage) {
+        self.messages.push_back(message);
+        self.evict();
+    }
+
+    // Keep only the last max_messages; ones the summary doesn't cover yet
+    // are held until it does. If it falls too far behind, e.g. because the
+    // summary model keeps failing, the oldest are dropped unsummarized.
+    fn evict(&mut self) {
+        while self.messages.len() > self.max_messages {
+            let Some(evicted) = self.messages.pop_front() else {
+                break;
+            };
+            if self.summarize && self.evicted_count >= self.summarized() {
+                self.evicted.push(evicted);
+            }
+            self.evicted_count += 1;
+        }
+
+        let excess = self.evicted.len().saturating_sub(self.max_messages);
+        self.evicted.drain(..excess);
+    }
+
+    fn summarized(&self) -> usize {
+        self.summary.as_ref().map_or(0, |s| s.covered)
+    }
+
+    // Index into `messages` of the first message the summary doesn't cover
+    fn unsummarized_start(&self) -> usize {
+        self.summarized()
+            .saturating_sub(self.evicted_count)
+            .min(self.messages.len())
+    }
+
+    // Index into `messages` of the oldest message that still fits the budget
+    // next to the summary
+    fn context_start(&self, model: &str, context_window: usize, settings: &ContextSettings) -> usize {
+        let start = self.unsummarized_start();
+        let candidates: Vec<ChatMessage> = self.messages.range(start..).map(to_chat_message).collect();
+
+        let estimator = TokenEstimator::for_model(model);
+        let summary_tokens = self.summary.as_ref().map_or(0, |s| estimator.count(&s.text));
+        let budget = settings.budget_for(context_window).saturating_sub(summary_tokens);
+
+        self.messages.len() - context::fit_to_budget(candidates, &estimator, budget).len()
+    }
+
+    /// Recent messages as chat turns for `model`, trimmed to the token budget
+    /// and its `context_window`, and preceded by the summary of older turns.
+    ///
+    /// Providers expect turns to alternate starting with the user, so a leading
+    /// assistant reply is dropped and consecutive messages from the same role
+    /// (e.g. a question whose answer failed) are merged.
+    pub fn get_context(&self, model: &str, context_window: usize, settings: &ContextSettings) -> Vec<ChatMessage> {
+        let start = self.context_start(model, context_window, settings);
+        let mut turns: Vec<ChatMessage> = Vec::new();
+
+        for msg in self.messages.range(start..) {
+            let turn = to_chat_message(msg);
+            match turns.last_mut() {
+                Some(last) if last.role == turn.role => {
+                    last.content.push_str("\n\n");
+                    last.content.push_str(&turn.content);
+                }
+                None if turn.role == Role::Assistant => {}
+                _ => turns.push(turn),
+            }
+        }
+
+        if let (Some(summary), Some(first)) = (&self.summary, turns.first_mut()) {
+            first.content = format!(
+                "Summary of the earlier conversation:\n{}\n\n{}",
+                summary.text, first.content
+            );
+        }
+
+        turns
+    }
+
+    /// The oldest messages that fell out of the context window for `model` and
+    /// aren't in the summary yet, as many as fit the token budget of
+    /// `summarizer` next to the summary so far. Returns None if there are none
+    /// or a summary is already being written.
+    pub fn summary_job(
+        &mut self,
+        model: &str,
+        context_window: usize,
+        summarizer: &str,
+        summarizer_window: usize,
+        settings: &ContextSettings,
+    ) -> Option<SummaryJob> {
+        if self.summarizing {
+            return None;
+        }
+
+        let start = self.unsummarized_start();
+        let end = self.context_start(model, context_window, settings);
+        if self.evicted.is_empty() && end <= start {
+            return None;
+        }
+
+        let previous = self.summary.as_ref().map(|s| s.text.clone());
+        let estimator = TokenEstimator::for_model(summarizer);
+        let mut budget = settings
+            .budget_for(summarizer_window)
+            .saturating_sub(previous.as_deref().map_or(0, |text| estimator.count(text)));
+
+        // Always take at least one message, or the backlog would never shrink
+        let mut messages = Vec::new();
+        for message in self.evicted.iter().chain(self.messages.range(start..end)) {
+            let cost = estimator.message_tokens(&to_chat_message(message));
+            if !messages.is_empty() && cost > budget {
+                break;
+            }
+            budget = budget.saturating_sub(cost);
+            messages.push(message.clone());
+        }
+        self.summarizing = true;
+
+        // `evicted` and `messages[start..]` are consecutive, so the job covers
+        // everything up to its last message
+        let first = self.evicted_count - self.evicted.len() + start;
+        Some(SummaryJob {
+            previous,
+            covered: first + messages.len(),
+            messages,
+            epoch: self.epoch,
+        })
+    }
+
+    /// Store the outcome of a `SummaryJob`.
+    pub fn finish_summary(&mut self, job: SummaryJob, result: Result<String, GhostError>) {
+        if job.epoch != self.epoch {
+            return;
+        }
+        self.summarizing = false;
+
+        let text = match result {
+            Ok(text) => text,
+            Err(e) => {
+                log::warn!("Failed to summarize conversation: {}", e);
+                return;
+            }
+        };
+
+        let summary = Summary {
+            text,
+            covered: job.covered,
+        };
+
+        // `evicted` holds the messages just before the in-memory ones
+        let first_evicted = self.evicted_count - self.evicted.len();
+        let now_covered = summary.covered.saturating_sub(first_evicted).min(self.evicted.len());
+        self.evicted.drain(..now_covered);
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save_summary(&summary) {
+                log::error!("Failed to save conversation summary: {}", e);
+            }
+        }
+        self.summary = Some(summary);
+    }
+
+    /// Most recent message from `role` still in memory.
+    pub fn last_message(&self, role: &str) -> Option<&ConversationMessage> {
+        self.messages.iter().rev().find(|m| m.role == role)
+    }
+
+    /// Full history. Comes from disk when persisted, since the in-memory
+    /// window only holds the last `max_messages`.
+    pub fn history(&self) -> Result<Vec<ConversationMessage>, GhostError> {
+        match &self.store {
+            Some(store) => store
+                .load()
+                .map_err(|e| GhostError::Storage(format!("Failed to read conversation history: {}", e))),
+            None => Ok(self.messages.iter().cloned().collect()),
+        }
+    }
+
+    pub fn clear(&mut self) -> Result<(), GhostError> {
+        self.messages.clear();
+        self.summary = None;
+        self.evicted.clear();
+        self.evicted_count = 0;
+        self.summarizing = false;
+        self.epoch += 1;
+        if let Some(store) = &self.store {
+            store
+                .clear()
+                .map_err(|e| GhostError::Storage(format!("Failed to clear conversation history: {}", e)))?;
+        }
+        Ok(())
+    }
+}
+
+fn to_chat_message(msg: &ConversationMessage) -> ChatMessage {
+    let role = match msg.role.as_str() {
+        "assistant" => Role::Assistant,
+        _ => Role::User,
+    };
+    ChatMessage {
+        role,
+        content: msg.content.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each message is about 30 tokens, so a budget of 40 fits one
+    fn conversation(max_messages: usize, summarize: bool) -> Conversation {
+        let mut conversation = Conversation::new();
+        conversation.set_limits(max_messages, summarize);
+        for i in 0..6 {
+            let role = if i % 2 == 0 { "user" } else { "assistant" };
+            conversation.add_message(role.to_string(), format!("{} {}", i, "x".repeat(100))).unwrap();
+        }
+        conversation
+    }
+
+    fn settings(token_budget: usize) -> ContextSettings {
+        ContextSettings {
+            token_budget,
+            reserve_output_tokens: 0,
+            ..ContextSettings::default()
+        }
+    }
+
+    fn job(conversation: &mut Conversation, budget: usize) -> Option<SummaryJob> {
+        conversation.summary_job("gemini-2.0-flash", 1_000_000, "gemini-2.0-flash", 1_000_000, &settings(budget))
+    }
+
+    fn first_words(messages: &[ConversationMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.split(' ').next().unwrap()).collect()
+    }
+
+    #[test]
+    fn eviction_without_summary_keeps_nothing() {
+        let conversation = conversation(2, false);
+        assert_eq!(conversation.messages.len(), 2);
+        assert!(conversation.evicted.is_empty());
+        assert_eq!(conversation.evicted_count, 4);
+
+        // Turning summaries off also drops what was held
+        let mut conversation = self::conversation(2, true);
+        assert_eq!(conversation.evicted.len(), 2);
+        conversation.set_limits(2, false);
+        assert!(conversation.evicted.is_empty());
+    }
+
+    #[test]
+    fn eviction_with_summary_holds_turns_until_summarized() {
+        let mut conversation = conversation(3, true);
+        assert_eq!(first_words(&conversation.evicted), vec!["0", "1", "2"]);
+
+        // Everything fits the budget; the in-memory assistant turn that can't
+        // open the context goes into the summary as well
+        let job = job(&mut conversation, 10_000).unwrap();
+        assert_eq!(first_words(&job.messages), vec!["0", "1", "2", "3"]);
+        assert!(self::job(&mut conversation, 10_000).is_none(), "one job at a time");
+
+        conversation.finish_summary(job, Ok("summary".to_string()));
+        assert!(conversation.evicted.is_empty());
+        assert_eq!(conversation.summarized(), 4);
+
+        // Evicting a turn the summary covers holds nothing, later ones are held
+        conversation.add_message("user".to_string(), "6".to_string()).unwrap();
+        assert!(conversation.evicted.is_empty());
+        conversation.add_message("assistant".to_string(), "7".to_string()).unwrap();
+        assert_eq!(first_words(&conversation.evicted), vec!["4"]);
+    }
+
+    #[test]
+    fn evicted_turns_are_capped() {
+        let conversation = conversation(2, true);
+        assert_eq!(first_words(&conversation.evicted), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn finish_summary_drains_only_the_covered_prefix() {
+        let mut conversation = conversation(3, true);
+
+        // Only one message fits the summary model's budget
+        let job = job(&mut conversation, 40).unwrap();
+        assert_eq!(first_words(&job.messages), vec!["0"]);
+
+        conversation.add_message("user".to_string(), "6".to_string()).unwrap();
+        conversation.finish_summary(job, Ok("summary".to_string()));
+        assert_eq!(first_words(&conversation.evicted), vec!["1", "2", "3"]);
+        assert_eq!(conversation.summarized(), 1);
+
+        let job = self::job(&mut conversation, 40).unwrap();
+        assert_eq!(first_words(&job.messages), vec!["1"]);
+    }
+
+    #[test]
+    fn failed_summary_keeps_the_backlog() {
+        let mut conversation = conversation(3, true);
+        let job = job(&mut conversation, 10_000).unwrap();
+        conversation.finish_summary(job, Err(GhostError::Cancelled));
+        assert_eq!(conversation.evicted.len(), 3);
+        assert!(self::job(&mut conversation, 10_000).is_some());
+    }
+
+    #[test]
+    fn summary_started_before_clear_is_discarded() {
+        let mut conversation = conversation(3, true);
+        let job = job(&mut conversation, 10_000).unwrap();
+
+        conversation.clear().unwrap();
+        conversation.add_message("user".to_string(), "new".to_string()).unwrap();
+        conversation.finish_summary(job, Ok("stale".to_string()));
+
+        assert!(conversation.summary.is_none());
+        assert_eq!(conversation.messages.len(), 1);
+        assert!(conversation.evicted.is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+// Errors returned by commands and sent in error events. They serialize as
+// tagged JSON, e.g. `{"kind": "rate_limited", "message": "...", "retry_after": 30}`,
+// so the frontend can react to the kind and still show the message.
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum GhostError {
+    /// The request never got an answer, e.g. no connection or a dropped
+    /// stream. `message` says which.
+    #[error("{message}")]
+    Network { service: String, message: String },
+    #[error("{service} took too long to respond")]
+    Timeout { service: String },
+    /// `retry_after` is in seconds, if the server said how long to wait.
+    #[error("{service} is rate limiting requests")]
+    RateLimited { service: String, retry_after: Option<u64> },
+    /// Missing, locked or rejected credentials.
+    #[error("{message}")]
+    Auth { service: String, message: String },
+    /// The provider answered with an error. `code` is the HTTP status when
+    /// the error came as a response rather than inside the stream.
+    #[error("{service} returned an error: {message}")]
+    ProviderError {
+        service: String,
+        code: Option<u16>,
+        message: String,
+    },
+    #[error("Request was cancelled")]
+    Cancelled,
+    #[error("Unsupported model: {0}")]
+    InvalidModel(String),
+    /// A request or setting that can't be used as given.
+    #[error("{0}")]
+    InvalidInput(String),
+    /// No session, persona or template with this ID.
+    #[error("Unknown {what}: {id}")]
+    NotFound { what: String, id: String },
+    /// The shortcut is bound twice or taken by another application.
+    #[error("{message}")]
+    HotkeyConflict { shortcut: String, message: String },
+    /// Reading or writing the app's own files failed.
+    #[error("{0}")]
+    Storage(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl GhostError {
+    /// Map a failed response to the matching error. `body` is the response text.
+    pub fn from_status(service: &str, status: StatusCode, headers: &HeaderMap, body: String) -> Self {
+        let service = service.to_string();
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => GhostError::RateLimited {
+                service,
+                retry_after: retry_after(headers),
+            },
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => GhostError::Auth {
+                message: format!("{} rejected the credentials: {}", service, body),
+                service,
+            },
+            _ => GhostError::ProviderError {
+                service,
+                code: Some(status.as_u16()),
+                message: format!("{} - {}", status, body),
+            },
+        }
+    }
+
+    /// Map a failure to send a request.
+    pub fn from_reqwest(service: &str, error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return GhostError::Timeout {
+                service: service.to_string(),
+            };
+        }
+        GhostError::Network {
+            message: format!("Failed to connect to {}: {}", service, error),
+            service: service.to_string(),
+        }
+    }
+
+    /// Map a failure to read the body of a response that already started.
+    pub fn from_body(service: &str, error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return GhostError::Timeout {
+                service: service.to_string(),
+            };
+        }
+        GhostError::Network {
+            message: format!("Lost the connection to {} while reading the response: {}", service, error),
+            service: service.to_string(),
+        }
+    }
+
+    pub fn not_found(what: &str, id: &str) -> Self {
+        GhostError::NotFound {
+            what: what.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            GhostError::Network { .. } => "network",
+            GhostError::Timeout { .. } => "timeout",
+            GhostError::RateLimited { .. } => "rate_limited",
+            GhostError::Auth { .. } => "auth",
+            GhostError::ProviderError { .. } => "provider_error",
+            GhostError::Cancelled => "cancelled",
+            GhostError::InvalidModel(_) => "invalid_model",
+            GhostError::InvalidInput(_) => "invalid_input",
+            GhostError::NotFound { .. } => "not_found",
+            GhostError::HotkeyConflict { .. } => "hotkey_conflict",
+            GhostError::Storage(_) => "storage",
+            GhostError::Other(_) => "other",
+        }
+    }
+}
+
+// Retry-After holds either a number of seconds or an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(seconds);
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let seconds = (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_seconds();
+    Some(seconds.max(0) as u64)
+}
+
+impl Serialize for GhostError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("kind", self.kind())?;
+        map.serialize_entry("message", &self.to_string())?;
+        match self {
+            GhostError::Network { service, .. } | GhostError::Timeout { service } | GhostError::Auth { service, .. } => {
+                map.serialize_entry("service", service)?;
+            }
+            GhostError::RateLimited { service, retry_after } => {
+                map.serialize_entry("service", service)?;
+                map.serialize_entry("retry_after", retry_after)?;
+            }
+            GhostError::ProviderError { service, code, .. } => {
+                map.serialize_entry("service", service)?;
+                map.serialize_entry("code", code)?;
+            }
+            GhostError::InvalidModel(model) => map.serialize_entry("model", model)?,
+            GhostError::NotFound { id, .. } => map.serialize_entry("id", id)?,
+            GhostError::HotkeyConflict { shortcut, .. } => map.serialize_entry("shortcut", shortcut)?,
+            GhostError::Cancelled | GhostError::InvalidInput(_) | GhostError::Storage(_) | GhostError::Other(_) => {}
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn rate_limit_reads_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+        let error = GhostError::from_status("Gemini API", StatusCode::TOO_MANY_REQUESTS, &headers, String::new());
+
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "rate_limited",
+                "message": "Gemini API is rate limiting requests",
+                "service": "Gemini API",
+                "retry_after": 30,
+            })
+        );
+    }
+}
@@ -0,0 +1,126 @@
+// Sampling parameters for a request. Every field is optional so that request
+// options, per-model defaults and global defaults can be layered; whatever is
+// still unset when the request goes out is left to the provider.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct GenerationOptions {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// Generation stops before any of these strings would be produced.
+    pub stop: Vec<String>,
+    pub seed: Option<u64>,
+}
+
+/// What a provider accepts, checked before a request is sent.
+#[derive(Debug, Clone, Copy)]
+pub struct OptionLimits {
+    pub max_temperature: f32,
+    pub max_stop_sequences: usize,
+    pub seed: bool,
+}
+
+impl Default for OptionLimits {
+    fn default() -> Self {
+        Self {
+            max_temperature: 2.0,
+            max_stop_sequences: 4,
+            seed: true,
+        }
+    }
+}
+
+impl GenerationOptions {
+    /// `self` with every field that `overrides` sets replaced.
+    pub fn merge(&self, overrides: &GenerationOptions) -> GenerationOptions {
+        GenerationOptions {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            max_tokens: overrides.max_tokens.or(self.max_tokens),
+            stop: if overrides.stop.is_empty() {
+                self.stop.clone()
+            } else {
+                overrides.stop.clone()
+            },
+            seed: overrides.seed.or(self.seed),
+        }
+    }
+
+    /// Check the options against `limits`. `provider` names the provider in errors.
+    pub fn validate(&self, limits: &OptionLimits, provider: &str) -> Result<(), String> {
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=limits.max_temperature).contains(&temperature) {
+                return Err(format!(
+                    "Temperature for {} must be between 0 and {}",
+                    provider, limits.max_temperature
+                ));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(top_p > 0.0 && top_p <= 1.0) {
+                return Err("Top P must be greater than 0 and at most 1".to_string());
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err("Max tokens must be greater than zero".to_string());
+        }
+        if self.stop.len() > limits.max_stop_sequences {
+            return Err(match limits.max_stop_sequences {
+                0 => format!("{} does not support stop sequences", provider),
+                max => format!("{} accepts at most {} stop sequences", provider, max),
+            });
+        }
+        if self.stop.iter().any(String::is_empty) {
+            return Err("Stop sequences must not be empty".to_string());
+        }
+        if self.seed.is_some() && !limits.seed {
+            return Err(format!("{} does not support a seed", provider));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_prefers_overrides() {
+        let defaults = GenerationOptions {
+            temperature: Some(0.7),
+            max_tokens: Some(2048),
+            stop: vec!["END".to_string()],
+            ..Default::default()
+        };
+        let overrides = GenerationOptions {
+            temperature: Some(0.2),
+            seed: Some(7),
+            ..Default::default()
+        };
+
+        let merged = defaults.merge(&overrides);
+        assert_eq!(merged.temperature, Some(0.2));
+        assert_eq!(merged.max_tokens, Some(2048));
+        assert_eq!(merged.stop, vec!["END".to_string()]);
+        assert_eq!(merged.seed, Some(7));
+    }
+
+    #[test]
+    fn validate_checks_provider_limits() {
+        let limits = OptionLimits {
+            max_temperature: 1.0,
+            max_stop_sequences: 0,
+            seed: false,
+        };
+        let options = |options: GenerationOptions| options.validate(&limits, "anthropic");
+
+        assert!(options(GenerationOptions { temperature: Some(1.0), ..Default::default() }).is_ok());
+        assert!(options(GenerationOptions { temperature: Some(1.5), ..Default::default() }).is_err());
+        assert!(options(GenerationOptions { top_p: Some(0.0), ..Default::default() }).is_err());
+        assert!(options(GenerationOptions { stop: vec!["x".to_string()], ..Default::default() }).is_err());
+        assert!(options(GenerationOptions { seed: Some(1), ..Default::default() }).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+// Global shortcuts and the actions they trigger. Shortcuts are written like
+// "Ctrl+Alt+G": any number of modifiers (Ctrl, Alt/Option, Shift, Cmd/Super,
+// CmdOrCtrl) followed by one key.
+
+use crate::error::GhostError;
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::GlobalHotKeyManager;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    /// Show or hide the window.
+    ToggleWindow,
+    /// Show the window and ask about whatever is on the clipboard.
+    AskClipboard,
+    /// Start a new session and show the window.
+    NewSession,
+    /// Send the active session's last prompt again.
+    RerunLastPrompt,
+    /// Cancel every answer that is still streaming.
+    StopGeneration,
+    /// Paste the last answer into the application that had focus, by way
+    /// of the clipboard.
+    PasteLastAnswer,
+}
+
+pub fn parse(shortcut: &str) -> Result<HotKey, GhostError> {
+    // global-hotkey panics on a shortcut without a key, so check for one
+    // first. A key parses as a shortcut on its own; a modifier doesn't.
+    let has_key = shortcut.split('+').any(|token| token.trim().parse::<HotKey>().is_ok());
+    if !has_key {
+        return Err(GhostError::InvalidInput(format!(
+            "Shortcut '{}' needs a key besides its modifiers",
+            shortcut
+        )));
+    }
+
+    shortcut
+        .parse()
+        .map_err(|e| GhostError::InvalidInput(format!("Invalid shortcut '{}': {}", shortcut, e)))
+}
+
+pub struct Binding {
+    shortcut: String,
+    hotkey: HotKey,
+    action: HotkeyAction,
+}
+
+/// Parse every shortcut in `bindings`, keyed by hotkey ID. Fails if two
+/// spellings name the same combination, e.g. "Ctrl+G" and "Control+G".
+pub fn parse_bindings(bindings: &BTreeMap<String, HotkeyAction>) -> Result<HashMap<u32, Binding>, GhostError> {
+    let mut parsed = HashMap::new();
+    for (shortcut, action) in bindings {
+        let hotkey = parse(shortcut)?;
+        let binding = Binding {
+            shortcut: shortcut.clone(),
+            hotkey,
+            action: *action,
+        };
+        if parsed.insert(hotkey.id(), binding).is_some() {
+            return Err(GhostError::HotkeyConflict {
+                shortcut: shortcut.clone(),
+                message: format!("Shortcut '{}' is bound more than once", shortcut),
+            });
+        }
+    }
+    Ok(parsed)
+}
+
+pub struct HotkeyManager {
+    manager: GlobalHotKeyManager,
+    bindings: HashMap<u32, Binding>,
+}
+
+impl HotkeyManager {
+    pub fn new() -> Result<Self, GhostError> {
+        let manager = GlobalHotKeyManager::new()
+            .map_err(|e| GhostError::Other(format!("Global shortcuts are unavailable: {}", e)))?;
+        Ok(Self {
+            manager,
+            bindings: HashMap::new(),
+        })
+    }
+
+    /// Action bound to the hotkey with `id`, as reported in `GlobalHotKeyEvent`s.
+    pub fn action(&self, id: u32) -> Option<HotkeyAction> {
+        self.bindings.get(&id).map(|binding| binding.action)
+    }
+
+    /// Replace all registered shortcuts with `bindings`. If any of them can't
+    /// be registered, e.g. because another application owns the combination,
+    /// the previous shortcuts stay active.
+    pub fn set(&mut self, bindings: &BTreeMap<String, HotkeyAction>) -> Result<(), GhostError> {
+        let parsed = parse_bindings(bindings)?;
+
+        let mut added = Vec::new();
+        for (id, binding) in &parsed {
+            if self.bindings.contains_key(id) {
+                continue;
+            }
+            if let Err(e) = self.manager.register(binding.hotkey) {
+                for hotkey in added {
+                    let _ = self.manager.unregister(hotkey);
+                }
+                return Err(GhostError::HotkeyConflict {
+                    shortcut: binding.shortcut.clone(),
+                    message: format!(
+                        "Couldn't register {}; it may already be in use by another application ({})",
+                        binding.shortcut, e
+                    ),
+                });
+            }
+            added.push(binding.hotkey);
+        }
+
+        for (id, binding) in &self.bindings {
+            if !parsed.contains_key(id) {
+                let _ = self.manager.unregister(binding.hotkey);
+            }
+        }
+        self.bindings = parsed;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcut_needs_a_key() {
+        assert!(parse("Ctrl+Alt+G").is_ok());
+        assert!(parse("F1").is_ok());
+        for shortcut in ["Shift", "Ctrl+Shift", "Ctrl+CmdOrControl", "Shift+CommandOrCtrl", "Alt+", ""] {
+            assert!(
+                matches!(parse(shortcut), Err(GhostError::InvalidInput(_))),
+                "{} should be rejected",
+                shortcut
+            );
+        }
+        assert!(parse("Ctrl+G+H").is_err());
+    }
+
+    #[test]
+    fn same_combination_bound_twice_conflicts() {
+        let bindings = BTreeMap::from([
+            ("Ctrl+G".to_string(), HotkeyAction::ToggleWindow),
+            ("Control+G".to_string(), HotkeyAction::NewSession),
+        ]);
+        assert!(matches!(
+            parse_bindings(&bindings),
+            Err(GhostError::HotkeyConflict { .. })
+        ));
+
+        let bindings = BTreeMap::from([
+            ("Ctrl+G".to_string(), HotkeyAction::ToggleWindow),
+            ("Ctrl+Shift+G".to_string(), HotkeyAction::NewSession),
+        ]);
+        assert_eq!(parse_bindings(&bindings).unwrap().len(), 2);
+    }
+}
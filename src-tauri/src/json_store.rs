@@ -0,0 +1,187 @@
+// A list of user-defined records (personas, templates) kept in one JSON file
+// in the app data directory. Changes are written out before they take effect
+// in memory, so a failed save leaves both as they were.
+
+use crate::error::GhostError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub trait Record: Clone + Serialize + DeserializeOwned {
+    /// Names the record in messages, e.g. "persona".
+    const KIND: &'static str;
+    const FILE: &'static str;
+
+    fn id(&self) -> &str;
+
+    fn validate(&self) -> Result<(), String>;
+}
+
+pub struct JsonStore<T> {
+    // None until the app data directory is known; records then live in memory only
+    path: Option<PathBuf>,
+    records: Vec<T>,
+}
+
+impl<T: Record> JsonStore<T> {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            records: Vec::new(),
+        }
+    }
+
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(T::FILE);
+        let records = read_or_set_aside(&path)?;
+
+        Ok(Self {
+            path: Some(path),
+            records,
+        })
+    }
+
+    pub fn list(&self) -> Vec<T> {
+        self.records.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<T> {
+        self.records.iter().find(|r| r.id() == id).cloned()
+    }
+
+    pub fn create(&mut self, record: T) -> Result<T, GhostError> {
+        record.validate().map_err(GhostError::InvalidInput)?;
+
+        let mut records = self.records.clone();
+        records.push(record.clone());
+        self.commit(records)?;
+        Ok(record)
+    }
+
+    pub fn update(&mut self, record: T) -> Result<T, GhostError> {
+        record.validate().map_err(GhostError::InvalidInput)?;
+
+        let mut records = self.records.clone();
+        let existing = records
+            .iter_mut()
+            .find(|r| r.id() == record.id())
+            .ok_or_else(|| GhostError::not_found(T::KIND, record.id()))?;
+        *existing = record.clone();
+        self.commit(records)?;
+        Ok(record)
+    }
+
+    pub fn delete(&mut self, id: &str) -> Result<(), GhostError> {
+        if !self.records.iter().any(|r| r.id() == id) {
+            return Err(GhostError::not_found(T::KIND, id));
+        }
+
+        let mut records = self.records.clone();
+        records.retain(|r| r.id() != id);
+        self.commit(records)
+    }
+
+    // Save `records`, then make them the current ones
+    fn commit(&mut self, records: Vec<T>) -> Result<(), GhostError> {
+        if let Some(path) = &self.path {
+            save_json(path, &records).map_err(|e| GhostError::Storage(format!("Failed to save {}s: {}", T::KIND, e)))?;
+        }
+
+        self.records = records;
+        Ok(())
+    }
+}
+
+/// Read the JSON file at `path`, or the default if there is none. A file that
+/// doesn't parse is renamed to `<name>.corrupt` and the default used instead,
+/// so one broken file doesn't keep the app from starting.
+pub fn read_or_set_aside<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e),
+    };
+
+    match serde_json::from_str(&contents) {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            let aside = set_aside(path)?;
+            log::warn!("Invalid {}: {}, moved it to {}", path.display(), e, aside.display());
+            Ok(T::default())
+        }
+    }
+}
+
+/// Rename `path` to `<name>.corrupt`, replacing an earlier one, and return
+/// the new path.
+pub fn set_aside(path: &Path) -> io::Result<PathBuf> {
+    let mut aside = path.as_os_str().to_owned();
+    aside.push(".corrupt");
+    fs::rename(path, &aside)?;
+    Ok(aside.into())
+}
+
+/// Write `value` to `path` as JSON. It goes to a temporary file first so a
+/// crash can't leave a half-written file behind.
+pub fn save_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(value)?)?;
+    fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        id: String,
+    }
+
+    impl Record for Note {
+        const KIND: &'static str = "note";
+        const FILE: &'static str = "notes.json";
+
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn validate(&self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn note(id: &str) -> Note {
+        Note { id: id.to_string() }
+    }
+
+    #[test]
+    fn failed_save_keeps_records_unchanged() {
+        let mut store = JsonStore::<Note>::open(Path::new("/nonexistent/ghost-query")).unwrap();
+        assert!(store.create(note("a")).is_err());
+        assert!(store.list().is_empty());
+
+        let mut store = JsonStore::<Note>::new();
+        store.create(note("a")).unwrap();
+        assert_eq!(store.update(note("b")), Err(GhostError::not_found("note", "b")));
+        store.delete("a").unwrap();
+        assert!(store.get("a").is_none());
+    }
+
+    #[test]
+    fn corrupt_file_is_set_aside() {
+        let dir = std::env::temp_dir().join(format!("ghost-query-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.json"), "[{\"id\":").unwrap();
+
+        let store = JsonStore::<Note>::open(&dir).unwrap();
+        assert!(store.list().is_empty());
+        assert!(!dir.join("notes.json").exists());
+        assert_eq!(fs::read_to_string(dir.join("notes.json.corrupt")).unwrap(), "[{\"id\":");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
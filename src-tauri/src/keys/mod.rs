@@ -0,0 +1,135 @@
+// API keys for calling providers directly (connection mode "direct"). Keys
+// live in the encrypted vault or the OS keyring depending on settings. If the
+// settings allow it, a `<PROVIDER>_API_KEY` environment variable is used as a
+// last resort.
+
+mod os_keyring;
+mod vault;
+
+use crate::error::GhostError;
+use crate::settings::{ConnectionMode, KeyStorage};
+use std::env;
+use std::path::Path;
+use std::sync::Mutex;
+use vault::Vault;
+
+struct KeyManager {
+    mode: ConnectionMode,
+    storage: KeyStorage,
+    env_api_keys: bool,
+    vault: Option<Vault>,
+}
+
+lazy_static::lazy_static! {
+    static ref KEYS: Mutex<KeyManager> = Mutex::new(KeyManager {
+        mode: ConnectionMode::default(),
+        storage: KeyStorage::default(),
+        env_api_keys: false,
+        vault: None,
+    });
+}
+
+/// Point the vault at its file in `dir`. Called once the app data directory is known.
+pub fn init(dir: &Path) {
+    KEYS.lock().unwrap().vault = Some(Vault::new(dir.join("keys.vault")));
+}
+
+/// Where keys are stored, and whether environment variables may stand in
+/// for missing ones. `mode` only shapes the error for a missing key.
+pub fn configure(mode: ConnectionMode, storage: KeyStorage, env_api_keys: bool) {
+    let mut manager = KEYS.lock().unwrap();
+    manager.mode = mode;
+    manager.storage = storage;
+    manager.env_api_keys = env_api_keys;
+}
+
+fn env_var(provider: &str) -> String {
+    format!("{}_API_KEY", provider.to_uppercase())
+}
+
+fn from_env(manager: &KeyManager, provider: &str) -> Option<String> {
+    if !manager.env_api_keys {
+        return None;
+    }
+    env::var(env_var(provider)).ok().filter(|key| !key.is_empty())
+}
+
+/// Key for `provider` if one is configured. Fails only if the key exists but
+/// can't be read, e.g. while the vault is locked.
+pub fn stored_api_key(provider: &str) -> Result<Option<String>, GhostError> {
+    let manager = KEYS.lock().unwrap();
+    let stored = match manager.storage {
+        KeyStorage::Vault => match &manager.vault {
+            Some(vault) => vault.get(provider),
+            None => Ok(None),
+        },
+        KeyStorage::Keyring => os_keyring::get(provider),
+    };
+    Ok(stored?.or_else(|| from_env(&manager, provider)))
+}
+
+pub fn api_key(provider: &str) -> Result<String, GhostError> {
+    stored_api_key(provider)?.ok_or_else(|| {
+        // Providers that need a key in proxy mode aren't served by the proxy
+        let hint = match KEYS.lock().unwrap().mode {
+            ConnectionMode::Direct => " or switch back to proxy mode",
+            ConnectionMode::Proxy => "",
+        };
+        GhostError::Auth {
+            service: provider.to_string(),
+            message: format!("No API key configured for {}. Add one in settings{}.", provider, hint),
+        }
+    })
+}
+
+pub fn set_api_key(provider: &str, api_key: &str) -> Result<(), GhostError> {
+    if api_key.trim().is_empty() {
+        return Err(GhostError::InvalidInput("API key must not be empty".to_string()));
+    }
+
+    let mut manager = KEYS.lock().unwrap();
+    match manager.storage {
+        KeyStorage::Vault => manager.vault()?.set(provider, api_key.trim()),
+        KeyStorage::Keyring => os_keyring::set(provider, api_key.trim()),
+    }
+}
+
+pub fn delete_api_key(provider: &str) -> Result<(), GhostError> {
+    let mut manager = KEYS.lock().unwrap();
+    match manager.storage {
+        KeyStorage::Vault => manager.vault()?.delete(provider),
+        KeyStorage::Keyring => os_keyring::delete(provider),
+    }
+}
+
+/// Whether a key for `provider` is stored, or set in the environment if that
+/// is allowed. A key in a locked vault counts as configured.
+pub fn has_api_key(provider: &str) -> bool {
+    let manager = KEYS.lock().unwrap();
+    let stored = match manager.storage {
+        KeyStorage::Vault => manager
+            .vault
+            .as_ref()
+            .is_some_and(|vault| vault.providers().iter().any(|p| p == provider)),
+        KeyStorage::Keyring => matches!(os_keyring::get(provider), Ok(Some(_))),
+    };
+    stored || from_env(&manager, provider).is_some()
+}
+
+pub fn unlock_vault(passphrase: &str) -> Result<(), GhostError> {
+    KEYS.lock().unwrap().vault()?.unlock(passphrase)
+}
+
+pub fn lock_vault() {
+    if let Some(vault) = KEYS.lock().unwrap().vault.as_mut() {
+        vault.lock();
+    }
+}
+
+impl KeyManager {
+    fn vault(&mut self) -> Result<&mut Vault, GhostError> {
+        self.vault
+            .as_mut()
+            .ok_or_else(|| GhostError::Storage("Key vault is not available yet".to_string()))
+    }
+}
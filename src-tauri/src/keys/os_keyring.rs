@@ -0,0 +1,32 @@
+// API keys in the operating system's credential store (macOS Keychain,
+// Windows Credential Manager, Secret Service on Linux).
+
+use crate::error::GhostError;
+use keyring::{Entry, Error};
+
+const SERVICE: &str = "ghost-query";
+
+fn entry(provider: &str) -> Result<Entry, GhostError> {
+    Entry::new(SERVICE, provider).map_err(|e| GhostError::Storage(format!("Keyring unavailable: {}", e)))
+}
+
+pub fn get(provider: &str) -> Result<Option<String>, GhostError> {
+    match entry(provider)?.get_password() {
+        Ok(api_key) => Ok(Some(api_key)),
+        Err(Error::NoEntry) => Ok(None),
+        Err(e) => Err(GhostError::Storage(format!("Failed to read key from keyring: {}", e))),
+    }
+}
+
+pub fn set(provider: &str, api_key: &str) -> Result<(), GhostError> {
+    entry(provider)?
+        .set_password(api_key)
+        .map_err(|e| GhostError::Storage(format!("Failed to store key in keyring: {}", e)))
+}
+
+pub fn delete(provider: &str) -> Result<(), GhostError> {
+    match entry(provider)?.delete_credential() {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(e) => Err(GhostError::Storage(format!("Failed to delete key from keyring: {}", e))),
+    }
+}
@@ -0,0 +1,286 @@
+// Encrypted key vault. API keys are stored as a JSON map encrypted with
+// XChaCha20-Poly1305 under a key derived from the user's passphrase with
+// Argon2id. Only the provider names are readable without the passphrase.
+
+use crate::error::GhostError;
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    providers: Vec<String>,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+struct Unlocked {
+    key: Key,
+    salt: Vec<u8>,
+    keys: BTreeMap<String, String>,
+}
+
+pub struct Vault {
+    path: PathBuf,
+    unlocked: Option<Unlocked>,
+}
+
+impl Vault {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, unlocked: None }
+    }
+
+    /// Decrypt the vault with `passphrase`, or create an empty vault protected
+    /// by it if none exists yet.
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), GhostError> {
+        if passphrase.is_empty() {
+            return Err(GhostError::InvalidInput("Passphrase must not be empty".to_string()));
+        }
+
+        let Some(file) = self.read_file()? else {
+            let mut salt = vec![0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            self.unlocked = Some(Unlocked {
+                key: derive_key(passphrase, &salt)?,
+                salt,
+                keys: BTreeMap::new(),
+            });
+            return self.save();
+        };
+
+        if file.version != VERSION {
+            return Err(GhostError::Storage(format!("Unsupported key vault version {}", file.version)));
+        }
+
+        let salt = decode(&file.salt)?;
+        let nonce = decode(&file.nonce)?;
+        let ciphertext = decode(&file.ciphertext)?;
+        if nonce.len() != 24 {
+            return Err(corrupted());
+        }
+
+        let key = derive_key(passphrase, &salt)?;
+        let plaintext = XChaCha20Poly1305::new(&key)
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| locked("Incorrect passphrase"))?;
+        let keys = serde_json::from_slice(&plaintext).map_err(|_| corrupted())?;
+
+        self.unlocked = Some(Unlocked { key, salt, keys });
+        Ok(())
+    }
+
+    pub fn lock(&mut self) {
+        self.unlocked = None;
+    }
+
+    /// Key for `provider`. Errors if the vault is locked and holds a key for it.
+    pub fn get(&self, provider: &str) -> Result<Option<String>, GhostError> {
+        match &self.unlocked {
+            Some(unlocked) => Ok(unlocked.keys.get(provider).cloned()),
+            None if self.providers().iter().any(|p| p == provider) => {
+                Err(locked(&format!("The key vault is locked; unlock it to use {}", provider)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(&mut self, provider: &str, api_key: &str) -> Result<(), GhostError> {
+        self.unlocked_mut()?.keys.insert(provider.to_string(), api_key.to_string());
+        self.save()
+    }
+
+    pub fn delete(&mut self, provider: &str) -> Result<(), GhostError> {
+        self.unlocked_mut()?.keys.remove(provider);
+        self.save()
+    }
+
+    /// Providers with a stored key. Readable while locked.
+    pub fn providers(&self) -> Vec<String> {
+        match &self.unlocked {
+            Some(unlocked) => unlocked.keys.keys().cloned().collect(),
+            None => self.read_file().ok().flatten().map(|f| f.providers).unwrap_or_default(),
+        }
+    }
+
+    fn unlocked_mut(&mut self) -> Result<&mut Unlocked, GhostError> {
+        self.unlocked
+            .as_mut()
+            .ok_or_else(|| locked("The key vault is locked"))
+    }
+
+    fn read_file(&self) -> Result<Option<VaultFile>, GhostError> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map(Some)
+                .map_err(|_| corrupted()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(GhostError::Storage(format!("Failed to read key vault: {}", e))),
+        }
+    }
+
+    // Re-encrypt everything under a fresh nonce
+    fn save(&self) -> Result<(), GhostError> {
+        let unlocked = self.unlocked.as_ref().ok_or_else(|| locked("The key vault is locked"))?;
+
+        let plaintext = serde_json::to_vec(&unlocked.keys).map_err(|e| GhostError::Other(e.to_string()))?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&unlocked.key)
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| GhostError::Other("Failed to encrypt key vault".to_string()))?;
+
+        let file = VaultFile {
+            version: VERSION,
+            providers: unlocked.keys.keys().cloned().collect(),
+            salt: BASE64.encode(&unlocked.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+
+        let contents = serde_json::to_string_pretty(&file).map_err(|e| GhostError::Other(e.to_string()))?;
+        let tmp = self.path.with_extension("tmp");
+        write_private(&tmp, contents.as_bytes())
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .map_err(|e| GhostError::Storage(format!("Failed to save key vault: {}", e)))
+    }
+}
+
+// Write `path` readable by the owner only
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path)?;
+    // `mode` only applies to new files
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, GhostError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| GhostError::Other(format!("Failed to derive vault key: {}", e)))?;
+    Ok(key)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, GhostError> {
+    BASE64.decode(value).map_err(|_| corrupted())
+}
+
+// A locked vault or a wrong passphrase; unlocking with the right one fixes it
+fn locked(message: &str) -> GhostError {
+    GhostError::Auth {
+        service: "Key vault".to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn corrupted() -> GhostError {
+    GhostError::Storage("Key vault is corrupted".to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_path() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ghost-query-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("keys.vault")
+    }
+
+    fn read(path: &Path) -> VaultFile {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keys_round_trip_through_the_file() {
+        let path = vault_path();
+        let mut vault = Vault::new(path.clone());
+        vault.unlock("correct horse").unwrap();
+        vault.set("gemini", "secret-key").unwrap();
+
+        let mut reopened = Vault::new(path.clone());
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get("gemini").unwrap().as_deref(), Some("secret-key"));
+        assert!(!fs::read_to_string(&path).unwrap().contains("secret-key"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let path = vault_path();
+        let mut vault = Vault::new(path.clone());
+        vault.unlock("correct horse").unwrap();
+        vault.set("gemini", "secret-key").unwrap();
+
+        let mut reopened = Vault::new(path.clone());
+        assert!(matches!(reopened.unlock("battery staple"), Err(GhostError::Auth { .. })));
+        assert!(reopened.set("gemini", "other").is_err());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn locked_vault_lists_providers_but_refuses_keys() {
+        let path = vault_path();
+        let mut vault = Vault::new(path.clone());
+        vault.unlock("correct horse").unwrap();
+        vault.set("gemini", "secret-key").unwrap();
+        vault.lock();
+
+        assert_eq!(vault.providers(), vec!["gemini".to_string()]);
+        assert!(matches!(vault.get("gemini"), Err(GhostError::Auth { .. })));
+        assert_eq!(vault.get("perplexity"), Ok(None));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn salt_is_kept_and_nonce_renewed_across_reopen() {
+        let path = vault_path();
+        let mut vault = Vault::new(path.clone());
+        vault.unlock("correct horse").unwrap();
+        vault.set("gemini", "secret-key").unwrap();
+        let before = read(&path);
+
+        let mut reopened = Vault::new(path.clone());
+        reopened.unlock("correct horse").unwrap();
+        reopened.set("perplexity", "other-key").unwrap();
+        let after = read(&path);
+
+        assert_eq!(after.salt, before.salt);
+        assert_ne!(after.nonce, before.nonce);
+        assert_eq!(reopened.get("gemini").unwrap().as_deref(), Some("secret-key"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn vault_file_is_private() {
+        let path = vault_path();
+        Vault::new(path.clone()).unlock("correct horse").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
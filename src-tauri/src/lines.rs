@@ -0,0 +1,150 @@
+// Line framing shared by the streaming formats we read (SSE and
+// newline-delimited JSON).
+//
+// Network chunks don't line up with lines, so bytes are buffered until a full
+// line is available and only then decoded as UTF-8. This keeps lines and
+// multi-byte characters intact when they straddle two chunks. Lines end in
+// "\n", "\r\n" or a bare "\r", as the SSE spec allows.
+
+use futures_util::{stream, Stream, StreamExt};
+use std::collections::VecDeque;
+
+/// Turns lines into items of a particular format.
+pub trait LineParser {
+    type Item;
+
+    /// Handle one line, without its line ending.
+    fn parse_line(&mut self, line: &str) -> Option<Self::Item>;
+
+    /// Flush anything still pending once the body has ended.
+    fn finish(&mut self) -> Option<Self::Item> {
+        None
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LineDecoder<P> {
+    buffer: Vec<u8>,
+    parser: P,
+}
+
+impl<P: LineParser> LineDecoder<P> {
+    pub fn new(parser: P) -> Self {
+        Self {
+            buffer: Vec::new(),
+            parser,
+        }
+    }
+
+    /// Feed the next chunk of the body and return every item it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<P::Item> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut items = Vec::new();
+        let mut start = 0;
+
+        while let Some(offset) = self.buffer[start..].iter().position(|&b| b == b'\n' || b == b'\r') {
+            let end = start + offset;
+            let next = match self.buffer[end] {
+                // A trailing '\r' might be the first half of "\r\n"; wait for more data
+                b'\r' if end + 1 == self.buffer.len() => break,
+                b'\r' if self.buffer[end + 1] == b'\n' => end + 2,
+                _ => end + 1,
+            };
+
+            let line = String::from_utf8_lossy(&self.buffer[start..end]).into_owned();
+            start = next;
+
+            items.extend(self.parser.parse_line(&line));
+        }
+
+        self.buffer.drain(..start);
+        items
+    }
+
+    /// Flush whatever is left once the body has ended, including a last line
+    /// without a line ending.
+    pub fn finish(&mut self) -> Vec<P::Item> {
+        let mut items = Vec::new();
+
+        if !self.buffer.is_empty() {
+            let mut rest = std::mem::take(&mut self.buffer);
+            if rest.last() == Some(&b'\r') {
+                rest.pop();
+            }
+            items.extend(self.parser.parse_line(&String::from_utf8_lossy(&rest)));
+        }
+
+        items.extend(self.parser.finish());
+        items
+    }
+}
+
+/// Decode a stream of body chunks into the items `parser` makes of its lines.
+/// An error from the body is passed on and ends the stream.
+pub fn decode<S, B, E, P>(bytes: S, parser: P) -> impl Stream<Item = Result<P::Item, E>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    P: LineParser,
+{
+    let state = (Box::pin(bytes), LineDecoder::new(parser), VecDeque::new(), false);
+
+    stream::unfold(state, |(mut bytes, mut decoder, mut pending, mut ended)| async move {
+        loop {
+            if let Some(item) = pending.pop_front() {
+                return Some((Ok(item), (bytes, decoder, pending, ended)));
+            }
+            if ended {
+                return None;
+            }
+
+            match bytes.next().await {
+                Some(Ok(chunk)) => pending.extend(decoder.feed(chunk.as_ref())),
+                Some(Err(e)) => return Some((Err(e), (bytes, decoder, pending, true))),
+                None => {
+                    ended = true;
+                    pending.extend(decoder.finish());
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keeps every line as is
+    struct Raw;
+
+    impl LineParser for Raw {
+        type Item = String;
+
+        fn parse_line(&mut self, line: &str) -> Option<String> {
+            Some(line.to_string())
+        }
+    }
+
+    #[test]
+    fn line_endings_split_across_chunks() {
+        let input = b"a\r\nb\rc\nd";
+        for split in 1..input.len() {
+            let mut decoder = LineDecoder::new(Raw);
+            let mut lines = decoder.feed(&input[..split]);
+            lines.extend(decoder.feed(&input[split..]));
+            lines.extend(decoder.finish());
+            assert_eq!(lines, vec!["a", "b", "c", "d"], "split at {}", split);
+        }
+    }
+
+    #[tokio::test]
+    async fn decode_stream_forwards_errors() {
+        let chunks: Vec<Result<&[u8], &str>> = vec![Ok(b"one\ntw"), Ok(b"o\n"), Err("connection reset")];
+        let lines: Vec<_> = decode(stream::iter(chunks), Raw).collect().await;
+        assert_eq!(
+            lines,
+            vec![Ok("one".to_string()), Ok("two".to_string()), Err("connection reset")]
+        );
+    }
+}
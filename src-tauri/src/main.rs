@@ -2,16 +2,52 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use tauri::{Manager, Emitter};
-use global_hotkey::{GlobalHotKeyManager, GlobalHotKeyEvent, hotkey::{HotKey, Modifiers, Code}};
-use serde::{Deserialize, Serialize};
-// use futures_util::StreamExt; // Not needed for non-streaming
-use std::collections::VecDeque;
-use std::sync::atomic::{AtomicBool, Ordering};
+use global_hotkey::{GlobalHotKeyEvent, HotKeyState};
+use serde::Serialize;
+use futures_util::StreamExt;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use uuid::Uuid;
+use std::time::Duration;
 use reqwest::Client;
-use std::env;
-use dotenv::dotenv;
+use client::SharedClient;
+
+mod client;
+mod context;
+mod conversation;
+mod error;
+mod generation;
+mod hotkeys;
+mod json_store;
+mod keys;
+mod lines;
+mod models;
+mod ndjson;
+mod paste;
+mod personas;
+mod providers;
+mod retry;
+mod sessions;
+mod settings;
+mod sse;
+mod store;
+mod streams;
+mod summarize;
+mod templates;
+
+use conversation::ConversationMessage;
+use error::GhostError;
+use generation::GenerationOptions;
+use hotkeys::{HotkeyAction, HotkeyManager};
+use personas::{Persona, PersonaManager};
+use sessions::{SessionInfo, SessionManager};
+use settings::{NetworkSettings, Settings};
+
+use models::ModelCatalog;
+use providers::{ChatRequest, EventStream, ModelInfo, Provider, ProviderRegistry, StopReason, StreamEvent, Usage};
+use retry::Retry;
+use streams::{ActiveStreams, StreamHandle};
+use templates::{Template, TemplateManager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
 
 // --- The following is for Windows-specific stealthing ---
 #[cfg(target_os = "windows")]
@@ -19,356 +55,908 @@ use windows::Win32::UI::WindowsAndMessaging::{SetWindowLongPtrA, GWL_EXSTYLE, WS
 #[cfg(target_os = "windows")]
 use winapi::shared::windef::HWND;
 
-// API Provider structures
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiRequest {
-    contents: Vec<GeminiContent>,
-    generation_config: GeminiGenerationConfig,
-}
+// Global conversation state (in a real app, you'd want proper state management)
+use std::sync::Mutex;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiContent {
-    parts: Vec<GeminiPart>,
+lazy_static::lazy_static! {
+    static ref SESSIONS: Arc<Mutex<SessionManager>> = Arc::new(Mutex::new(SessionManager::new()));
+    static ref ACTIVE_STREAMS: ActiveStreams = ActiveStreams::new();
+    static ref PROVIDERS: Arc<Mutex<ProviderRegistry>> =
+        Arc::new(Mutex::new(ProviderRegistry::from_settings(&Settings::default())));
+    static ref SETTINGS: Arc<Mutex<Settings>> = Arc::new(Mutex::new(Settings::default()));
+    static ref MODELS: Arc<Mutex<ModelCatalog>> = Arc::new(Mutex::new(ModelCatalog::new()));
+    static ref PERSONAS: Arc<Mutex<PersonaManager>> = Arc::new(Mutex::new(PersonaManager::new()));
+    static ref TEMPLATES: Arc<Mutex<TemplateManager>> = Arc::new(Mutex::new(TemplateManager::new()));
+    // None if the platform doesn't support global shortcuts
+    static ref HOTKEYS: Arc<Mutex<Option<HotkeyManager>>> = Arc::new(Mutex::new(None));
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiPart {
-    text: String,
+// Event payloads are tagged with the request ID so overlapping streams can be told apart
+#[derive(Debug, Serialize, Clone)]
+struct StreamPayload {
+    request_id: String,
+    content: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiGenerationConfig {
-    temperature: f32,
-    max_output_tokens: i32,
+#[derive(Debug, Serialize, Clone)]
+struct StreamDonePayload {
+    request_id: String,
+    /// The model that answered, a fallback if the requested one failed.
+    model: String,
+    content: String,
+    stop_reason: Option<StopReason>,
+    usage: Option<Usage>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiResponse {
-    candidates: Vec<GeminiCandidate>,
+#[derive(Debug, Serialize, Clone)]
+struct StreamErrorPayload {
+    request_id: String,
+    error: GhostError,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiCandidate {
-    content: GeminiContent,
-    finish_reason: Option<String>,
+// Sent when a model failed before answering and the next fallback takes over
+#[derive(Debug, Serialize, Clone)]
+struct StreamFallbackPayload {
+    request_id: String,
+    from: String,
+    to: String,
+    error: GhostError,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PerplexityRequest {
-    model: String,
-    messages: Vec<PerplexityMessage>,
-    stream: bool,
+// Sent before a failed request is tried again
+#[derive(Debug, Serialize, Clone)]
+struct StreamRetryPayload {
+    request_id: String,
+    #[serde(flatten)]
+    retry: Retry,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PerplexityMessage {
-    role: String,
-    content: String,
+#[derive(Debug, Serialize, Clone)]
+struct HotkeyErrorPayload {
+    error: GhostError,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PerplexityResponse {
-    choices: Vec<PerplexityChoice>,
-}
+// Problems found during setup, kept until the frontend asks for them since
+// events emitted before it is listening are lost
+struct StartupErrors(Mutex<Vec<GhostError>>);
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PerplexityChoice {
-    message: PerplexityMessage,
-    finish_reason: Option<String>,
+// Tells the frontend which hotkey action ran. `prompt` is set for actions that
+// ask something, which the frontend sends with the selected model.
+#[derive(Debug, Serialize, Clone)]
+struct HotkeyActionPayload {
+    action: HotkeyAction,
+    prompt: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct ConversationMessage {
-    id: String,
-    role: String, // "user" or "assistant"
-    content: String,
-    timestamp: u64,
+// The frontend may pick `request_id` itself so it can match events that
+// arrive before this returns
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // each argument is a named field of the invoke call
+async fn ask_ai_stream(
+    prompt: String,
+    model: Option<String>,
+    session_id: Option<String>,
+    options: Option<GenerationOptions>,
+    template_id: Option<String>,
+    variables: Option<HashMap<String, String>>,
+    request_id: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, GhostError> {
+    // With a template, the typed text only fills its {{input}} variable
+    let prompt = match template_id {
+        Some(template_id) => render_template(&template_id, prompt, variables.unwrap_or_default(), &app_handle)?,
+        None => prompt,
+    };
+
+    // The session's persona supplies the system prompt and, if the request
+    // doesn't name a model, the model
+    let (session_id, persona_id) = {
+        let sessions = SESSIONS.lock().unwrap();
+        let session_id = session_id.unwrap_or_else(|| sessions.active_id().to_string());
+        let persona_id = sessions.persona_id(&session_id);
+        (session_id, persona_id)
+    };
+    let persona = persona_id.and_then(|id| PERSONAS.lock().unwrap().get(&id));
+    let model = model
+        .or_else(|| persona.as_ref().and_then(|p| p.model.clone()))
+        .ok_or_else(|| GhostError::InvalidInput("No model selected".to_string()))?;
+
+    let mut provider = PROVIDERS.lock().unwrap().find(&model);
+    if provider.is_none() {
+        // The model may have been pulled into Ollama since we last looked
+        discover_models(app_handle.state::<SharedClient>().get()).await;
+        provider = PROVIDERS.lock().unwrap().find(&model);
+    }
+    let Some(provider) = provider else {
+        return Err(GhostError::InvalidModel(model));
+    };
+
+    let requested = options.unwrap_or_default();
+    let options = generation_options(&model, provider.as_ref(), persona.as_ref(), &requested)?;
+
+    // Fallbacks we can't serve, or that don't accept the options, are skipped
+    let (context_settings, fallbacks) = {
+        let settings = SETTINGS.lock().unwrap();
+        (settings.context.clone(), settings.fallbacks.get(&model).cloned().unwrap_or_default())
+    };
+    let context_window = MODELS.lock().unwrap().context_window(&model);
+    let mut candidates = vec![(model, provider, options, context_window)];
+    for model in fallbacks {
+        let Some(provider) = PROVIDERS.lock().unwrap().find(&model) else {
+            continue;
+        };
+        if let Ok(options) = generation_options(&model, provider.as_ref(), persona.as_ref(), &requested) {
+            let context_window = MODELS.lock().unwrap().context_window(&model);
+            candidates.push((model, provider, options, context_window));
+        }
+    }
+
+    // Add user message to the session's conversation; the context then ends
+    // with it. Each model gets the context that fits its window.
+    let attempts = {
+        let mut sessions = SESSIONS.lock().unwrap();
+        sessions.add_message(&session_id, "user".to_string(), prompt)?;
+        let conversation = sessions.conversation(&session_id)?;
+        let system = persona.map(|p| p.system_prompt);
+        candidates
+            .into_iter()
+            .map(|(model, provider, options, context_window)| {
+                let request = ChatRequest {
+                    messages: conversation.get_context(&model, context_window, &context_settings),
+                    model,
+                    system: system.clone(),
+                    options,
+                };
+                (provider, request)
+            })
+            .collect()
+    };
+
+    // Stream in the background so the caller gets the request ID right away
+    let (request_id, handle) = ACTIVE_STREAMS
+        .start(request_id)
+        .ok_or_else(|| GhostError::InvalidInput("A request with this ID is already running".to_string()))?;
+    let id = request_id.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Some(model) = run_stream(&id, &session_id, handle, attempts, &app_handle).await {
+            summarize_in_background(session_id, model, &app_handle);
+        }
+        ACTIVE_STREAMS.finish(&id);
+    });
+
+    Ok(request_id)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Conversation {
-    messages: VecDeque<ConversationMessage>,
-    max_messages: usize,
+// Request options win over the persona's, which win over the configured defaults for the model
+fn generation_options(
+    model: &str,
+    provider: &dyn Provider,
+    persona: Option<&Persona>,
+    requested: &GenerationOptions,
+) -> Result<GenerationOptions, GhostError> {
+    let mut options = SETTINGS.lock().unwrap().generation.options_for(model);
+    if let Some(persona) = persona {
+        options = options.merge(&persona.options);
+    }
+    let options = options.merge(requested);
+    options
+        .validate(&provider.option_limits(), provider.name())
+        .map_err(GhostError::InvalidInput)?;
+    Ok(options)
 }
 
-impl Conversation {
-    fn new() -> Self {
-        Self {
-            messages: VecDeque::new(),
-            max_messages: 20, // Keep last 20 messages for context
+// Fill in the template's variables from `values`, resolving built-ins the request didn't set
+fn render_template(
+    template_id: &str,
+    input: String,
+    mut values: HashMap<String, String>,
+    app_handle: &tauri::AppHandle,
+) -> Result<String, GhostError> {
+    let template = TEMPLATES
+        .lock()
+        .unwrap()
+        .get(template_id)
+        .ok_or_else(|| GhostError::not_found("template", template_id))?;
+
+    for name in templates::variables(&template.body).map_err(GhostError::InvalidInput)? {
+        if values.contains_key(&name) {
+            continue;
         }
+        let value = match name.as_str() {
+            "input" => input.clone(),
+            "date" => chrono::Local::now().format("%Y-%m-%d").to_string(),
+            // Other apps' selections can't be read portably, so the frontend
+            // passes the selection when it has one and we fall back to the clipboard
+            "clipboard" | "selection" => app_handle.clipboard().read_text().unwrap_or_default(),
+            _ => continue,
+        };
+        values.insert(name, value);
     }
 
-    fn add_message(&mut self, role: String, content: String) -> String {
-        let id = Uuid::new_v4().to_string();
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        let message = ConversationMessage {
-            id: id.clone(),
-            role,
-            content,
-            timestamp,
-        };
+    templates::render(&template.body, &values).map_err(GhostError::InvalidInput)
+}
 
-        self.messages.push_back(message);
+// Ask every provider for its current models and update the catalog
+async fn discover_models(client: Client) -> Vec<ModelInfo> {
+    let providers = PROVIDERS.lock().unwrap().all();
+    let models = models::discover(providers, &client).await;
+    MODELS.lock().unwrap().set(models.clone());
+    models
+}
 
-        // Keep only the last max_messages
-        if self.messages.len() > self.max_messages {
-            self.messages.pop_front();
+// Streams one answer into the session, moving on to the next of `attempts`
+// when a model fails before answering. Returns the model that answered if
+// the answer completed.
+async fn run_stream(
+    request_id: &str,
+    session_id: &str,
+    handle: StreamHandle,
+    attempts: Vec<(Arc<dyn Provider>, ChatRequest)>,
+    app_handle: &tauri::AppHandle,
+) -> Option<String> {
+    let mut attempts = attempts.into_iter();
+    let (provider, request) = attempts.next()?;
+    let mut model = request.model.clone();
+    let mut service = provider.name().to_string();
+    let mut stream = start_stream(request_id, provider, request, app_handle);
+    let mut full_content = String::new();
+    let mut stop_reason = None;
+    let mut usage = None;
+
+    let payload = |content: &str| StreamPayload {
+        request_id: request_id.to_string(),
+        content: content.to_string(),
+    };
+
+    loop {
+        let event = tokio::select! {
+            event = stream.next() => event,
+            _ = handle.token.cancelled() => Some(Err(GhostError::Cancelled)),
+        };
+
+        // A stream that ends without any text counts as failed, so the next
+        // model gets a go and nothing empty is stored
+        let ended = matches!(event, None | Some(Ok(StreamEvent::Done)));
+        if ended && !full_content.is_empty() {
+            break;
         }
+        let event = match event {
+            Some(event) if !ended => event,
+            _ => Err(GhostError::ProviderError {
+                service: service.clone(),
+                code: None,
+                message: format!("{} returned an empty answer", model),
+            }),
+        };
+
+        match event {
+            Ok(StreamEvent::Delta(content)) => {
+                full_content.push_str(&content);
+                let _ = app_handle.emit("ai-response-chunk", payload(&content));
+            }
+            Ok(StreamEvent::Stop { reason, usage: reported }) => {
+                stop_reason = Some(reason);
+                usage = reported.or(usage);
+            }
+            Ok(StreamEvent::Done) => break,
+            Err(GhostError::Cancelled) => {
+                // Dropping the stream closes the HTTP connection
+                drop(stream);
+
+                if handle.keep_partial() && !full_content.is_empty() {
+                    // The session may have been deleted while streaming
+                    let _ = SESSIONS
+                        .lock()
+                        .unwrap()
+                        .add_answer(session_id, full_content.clone(), model, true);
+                }
+                let _ = app_handle.emit("ai-response-cancelled", payload(&full_content));
+                return None;
+            }
+            Err(error) => {
+                // Nothing has been shown yet, so the next model can still answer
+                if full_content.is_empty() && (ended || should_fall_back(&error)) {
+                    if let Some((provider, request)) = attempts.next() {
+                        let fallback = StreamFallbackPayload {
+                            request_id: request_id.to_string(),
+                            from: model,
+                            to: request.model.clone(),
+                            error,
+                        };
+                        let _ = app_handle.emit("ai-response-fallback", fallback);
+                        model = request.model.clone();
+                        service = provider.name().to_string();
+                        stop_reason = None;
+                        usage = None;
+                        stream = start_stream(request_id, provider, request, app_handle);
+                        continue;
+                    }
+                }
 
-        id
+                let _ = app_handle.emit(
+                    "ai-response-error",
+                    StreamErrorPayload {
+                        request_id: request_id.to_string(),
+                        error,
+                    },
+                );
+                return None;
+            }
+        }
     }
 
-    fn get_context(&self) -> String {
-        self.messages
-            .iter()
-            .map(|msg| format!("{}: {}", msg.role, msg.content))
-            .collect::<Vec<_>>()
-            .join("\n")
+    // Stream finished, either via [DONE] or by the connection closing. An
+    // answer that ran into the token limit is kept but marked as truncated.
+    let truncated = stop_reason == Some(StopReason::MaxTokens);
+    let saved = SESSIONS
+        .lock()
+        .unwrap()
+        .add_answer(session_id, full_content.clone(), model.clone(), truncated);
+    if let Err(error) = saved {
+        let _ = app_handle.emit(
+            "ai-response-error",
+            StreamErrorPayload {
+                request_id: request_id.to_string(),
+                error,
+            },
+        );
+        return None;
     }
+    let _ = app_handle.emit(
+        "ai-response-done",
+        StreamDonePayload {
+            request_id: request_id.to_string(),
+            model: model.clone(),
+            content: full_content,
+            stop_reason,
+            usage,
+        },
+    );
+    Some(model)
+}
 
-    fn clear(&mut self) {
-        self.messages.clear();
+// Provider stream for one model, retried per the settings with retries
+// reported as `ai-response-retry`
+fn start_stream(
+    request_id: &str,
+    provider: Arc<dyn Provider>,
+    request: ChatRequest,
+    app_handle: &tauri::AppHandle,
+) -> EventStream {
+    let client = app_handle.state::<SharedClient>().get();
+    let (policy, stall_timeout) = {
+        let settings = SETTINGS.lock().unwrap();
+        let stall_timeout = Duration::from_secs(settings.network.stall_timeout_secs);
+        (settings.connection.retry.clone(), stall_timeout)
+    };
+    let id = request_id.to_string();
+    let emitter = app_handle.clone();
+    retry::with_retry(
+        policy,
+        move || {
+            let events = provider.stream(&client, request.clone());
+            client::with_stall_timeout(events, stall_timeout, provider.name().to_string())
+        },
+        move |retry| {
+            let payload = StreamRetryPayload {
+                request_id: id.clone(),
+                retry: retry.clone(),
+            };
+            let _ = emitter.emit("ai-response-retry", payload);
+        },
+    )
+}
+
+// Failures that another provider may well not have. An error inside the
+// stream without a status, e.g. from a proxy too old to pass on the
+// backend's, says the model couldn't answer but not why, so it counts too.
+fn should_fall_back(error: &GhostError) -> bool {
+    retry::is_transient(error)
+        || matches!(
+            error,
+            GhostError::Auth { .. } | GhostError::ProviderError { code: None, .. }
+        )
+}
+
+// Fold turns that no longer fit the context window into the session summary
+fn summarize_in_background(session_id: String, model: String, app_handle: &tauri::AppHandle) {
+    let settings = SETTINGS.lock().unwrap().context.clone();
+    if !settings.summarize_dropped_turns {
+        return;
     }
+
+    // Use the configured summary model if we can serve it, else the one that just answered
+    let providers = PROVIDERS.lock().unwrap();
+    let (provider, summary_model) = match providers.find(&settings.summary_model) {
+        Some(provider) => (provider, settings.summary_model.clone()),
+        None => match providers.find(&model) {
+            Some(provider) => (provider, model.clone()),
+            None => return,
+        },
+    };
+
+    drop(providers);
+
+    let (context_window, summary_window) = {
+        let models = MODELS.lock().unwrap();
+        (models.context_window(&model), models.context_window(&summary_model))
+    };
+    let client = app_handle.state::<SharedClient>().get();
+    // A long backlog is summarized a budget-sized chunk at a time
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let job = SESSIONS
+                .lock()
+                .unwrap()
+                .conversation(&session_id)
+                .ok()
+                .and_then(|conversation| {
+                    conversation.summary_job(&model, context_window, &summary_model, summary_window, &settings)
+                });
+            let Some(job) = job else {
+                break;
+            };
+
+            let result = summarize::summarize(provider.as_ref(), &client, summary_model.clone(), &job).await;
+            let failed = result.is_err();
+            if let Ok(conversation) = SESSIONS.lock().unwrap().conversation(&session_id) {
+                conversation.finish_summary(job, result);
+            }
+            if failed {
+                break;
+            }
+        }
+    });
 }
 
-// Global conversation state (in a real app, you'd want proper state management)
-use std::sync::Mutex;
+#[tauri::command]
+fn get_conversation_history(session_id: Option<String>) -> Result<Vec<ConversationMessage>, GhostError> {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session_id = session_id.unwrap_or_else(|| sessions.active_id().to_string());
+    sessions.conversation(&session_id)?.history()
+}
 
-lazy_static::lazy_static! {
-    static ref CONVERSATION: Arc<Mutex<Conversation>> = Arc::new(Mutex::new(Conversation::new()));
-    static ref STREAM_CANCELLED: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+#[tauri::command]
+fn clear_conversation(session_id: Option<String>) -> Result<(), GhostError> {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session_id = session_id.unwrap_or_else(|| sessions.active_id().to_string());
+    sessions.conversation(&session_id)?.clear()
 }
 
 #[tauri::command]
-async fn ask_ai_stream(prompt: String, model: String, app_handle: tauri::AppHandle) -> Result<(), String> {
-    // Reset cancellation flag
-    STREAM_CANCELLED.store(false, Ordering::Relaxed);
-    
-    // Add user message to conversation and get context
-    let contextual_prompt = {
-        let mut conversation = CONVERSATION.lock().unwrap();
-        conversation.add_message("user".to_string(), prompt.clone());
-        
-        // Build context-aware prompt
-        let context = conversation.get_context();
-        if context.is_empty() {
-            prompt.clone()
-        } else {
-            format!("Previous conversation:\n{}\n\nUser: {}", context, prompt)
+fn list_personas() -> Result<Vec<Persona>, GhostError> {
+    Ok(PERSONAS.lock().unwrap().list())
+}
+
+#[tauri::command]
+fn create_persona(
+    name: String,
+    system_prompt: String,
+    model: Option<String>,
+    options: Option<GenerationOptions>,
+) -> Result<Persona, GhostError> {
+    let persona = PERSONAS
+        .lock()
+        .unwrap()
+        .create(Persona::new(name, system_prompt, model, options.unwrap_or_default()))?;
+    Ok(persona)
+}
+
+#[tauri::command]
+fn update_persona(persona: Persona) -> Result<Persona, GhostError> {
+    PERSONAS.lock().unwrap().update(persona)
+}
+
+#[tauri::command]
+fn delete_persona(persona_id: String) -> Result<(), GhostError> {
+    PERSONAS.lock().unwrap().delete(&persona_id)
+}
+
+// Pass no persona ID to go back to plain conversations
+#[tauri::command]
+fn set_session_persona(session_id: String, persona_id: Option<String>) -> Result<SessionInfo, GhostError> {
+    if let Some(id) = &persona_id {
+        if PERSONAS.lock().unwrap().get(id).is_none() {
+            return Err(GhostError::not_found("persona", id));
         }
+    }
+    SESSIONS.lock().unwrap().set_persona(&session_id, persona_id)
+}
+
+#[tauri::command]
+fn list_templates() -> Result<Vec<Template>, GhostError> {
+    Ok(TEMPLATES.lock().unwrap().list())
+}
+
+#[tauri::command]
+fn create_template(name: String, body: String) -> Result<Template, GhostError> {
+    TEMPLATES.lock().unwrap().create(Template::new(name, body))
+}
+
+#[tauri::command]
+fn update_template(template: Template) -> Result<Template, GhostError> {
+    TEMPLATES.lock().unwrap().update(template)
+}
+
+#[tauri::command]
+fn delete_template(template_id: String) -> Result<(), GhostError> {
+    TEMPLATES.lock().unwrap().delete(&template_id)
+}
+
+// Register the hotkey bindings. Failures, typically another app owning a
+// combination, are also emitted as `hotkey-error`.
+fn apply_hotkeys(bindings: &BTreeMap<String, HotkeyAction>, app_handle: &tauri::AppHandle) -> Result<(), GhostError> {
+    let result = match HOTKEYS.lock().unwrap().as_mut() {
+        Some(hotkeys) => hotkeys.set(bindings),
+        None => Err(GhostError::Other("Global shortcuts are not available on this system".to_string())),
     };
-    
-    let client = Client::new();
-    
-    // Determine which API provider to use based on model
-    if model.starts_with("gemini") {
-        call_gemini_api(&client, &model, &contextual_prompt, &app_handle).await
-    } else if model == "sonar" {
-        call_perplexity_api(&client, &model, &contextual_prompt, &app_handle).await
+
+    if let Err(error) = &result {
+        let _ = app_handle.emit("hotkey-error", HotkeyErrorPayload { error: error.clone() });
+    }
+    result
+}
+
+// Bind `shortcut` to `action` (toggling the window by default), replacing the
+// action's previous shortcut. Runs on the main thread, where hotkeys have to be registered.
+#[tauri::command]
+fn set_hotkey(shortcut: String, action: Option<HotkeyAction>, app_handle: tauri::AppHandle) -> Result<(), GhostError> {
+    let action = action.unwrap_or(HotkeyAction::ToggleWindow);
+    let mut settings = SETTINGS.lock().unwrap().clone();
+    settings.hotkeys.bindings.retain(|_, bound| *bound != action);
+    settings.hotkeys.bindings.insert(shortcut, action);
+    change_settings(settings, &app_handle)
+}
+
+#[tauri::command]
+fn remove_hotkey(shortcut: String, app_handle: tauri::AppHandle) -> Result<(), GhostError> {
+    let mut settings = SETTINGS.lock().unwrap().clone();
+    if settings.hotkeys.bindings.remove(&shortcut).is_none() {
+        return Err(GhostError::InvalidInput(format!("No action is bound to {}", shortcut)));
+    }
+    change_settings(settings, &app_handle)
+}
+
+fn toggle_window(window: &tauri::WebviewWindow) {
+    if window.is_visible().unwrap() {
+        window.hide().unwrap();
     } else {
-        let error_msg = format!("Unsupported model: {}", model);
-        let _ = app_handle.emit("ai-response-error", &error_msg);
-        Err(error_msg)
+        show_window(window);
     }
 }
 
-async fn call_gemini_api(
-    client: &Client,
-    model: &str,
-    prompt: &str,
-    app_handle: &tauri::AppHandle,
-) -> Result<(), String> {
-    // Use proxy server instead of direct API calls
-    let proxy_url = env::var("PROXY_URL")
-        .unwrap_or_else(|_| "https://proxy-server-p9wzc2v53-prem-thatikondas-projects.vercel.app".to_string());
-    
-    let url = format!("{}/api/gemini", proxy_url);
-    
-    let request_body = serde_json::json!({
-        "model": model,
-        "prompt": prompt,
-        "temperature": 0.7,
-        "maxTokens": 2048,
-        "stream": true
-    });
+fn show_window(window: &tauri::WebviewWindow) {
+    // Center the window on screen when showing
+    window.center().unwrap();
+    window.show().unwrap();
+    window.set_focus().unwrap();
+    // Ensure the window stays on top and focused
+    window.set_always_on_top(true).unwrap();
+    window.unminimize().unwrap();
+    // Bring to front
+    window.set_always_on_top(false).unwrap(); // Reset to allow normal window behavior
+}
 
-    match client.post(&url).json(&request_body).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                let mut stream = response.bytes_stream();
-                let mut full_content = String::new();
-                
-                use futures_util::StreamExt;
-                
-                while let Some(chunk) = stream.next().await {
-                    let chunk = chunk.map_err(|e| format!("Stream error: {}", e))?;
-                    let chunk_str = String::from_utf8_lossy(&chunk);
-                    
-                    // Process each line in the chunk
-                    for line in chunk_str.lines() {
-                        if line.starts_with("data: ") {
-                            let data = &line[6..];
-                            if data == "[DONE]" {
-                                // Stream finished
-                                let mut conversation = CONVERSATION.lock().unwrap();
-                                conversation.add_message("assistant".to_string(), full_content.clone());
-                                let _ = app_handle.emit("ai-response-done", &full_content);
-                                return Ok(());
-                            }
-                            
-                            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(data) {
-                                if let Some(content) = parsed["content"].as_str() {
-                                    full_content.push_str(content);
-                                    let _ = app_handle.emit("ai-response-chunk", content);
-                                } else if let Some(error_msg) = parsed["error"].as_str() {
-                                    let _ = app_handle.emit("ai-response-error", error_msg);
-                                    return Err(format!("Proxy server error: {}", error_msg));
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                // If we get here, stream ended without [DONE]
-                let mut conversation = CONVERSATION.lock().unwrap();
-                conversation.add_message("assistant".to_string(), full_content.clone());
-                let _ = app_handle.emit("ai-response-done", &full_content);
-                Ok(())
-            } else {
-                let status = response.status();
-                let response_text = response.text().await.unwrap_or_else(|_| "Failed to read error response".to_string());
-                let error_msg = format!("Proxy server returned error: {} - {}", status, response_text);
-                let _ = app_handle.emit("ai-response-error", &error_msg);
-                Err(error_msg)
+// Carry out a hotkey action. What needs the frontend's state, like the
+// selected model, is finished there after the `hotkey-action` event.
+fn run_hotkey_action(action: HotkeyAction, window: &tauri::WebviewWindow) -> Result<(), GhostError> {
+    let app_handle = window.app_handle();
+    let mut prompt = None;
+
+    match action {
+        HotkeyAction::ToggleWindow => {
+            toggle_window(window);
+            return Ok(());
+        }
+        HotkeyAction::AskClipboard => {
+            let text = app_handle.clipboard().read_text().unwrap_or_default();
+            if text.trim().is_empty() {
+                return Err(GhostError::InvalidInput("The clipboard is empty".to_string()));
             }
+            prompt = Some(text);
+            show_window(window);
+        }
+        HotkeyAction::NewSession => {
+            SESSIONS.lock().unwrap().create(None)?;
+            show_window(window);
+        }
+        HotkeyAction::RerunLastPrompt => {
+            let mut sessions = SESSIONS.lock().unwrap();
+            let session_id = sessions.active_id().to_string();
+            let last = sessions.conversation(&session_id)?.last_message("user");
+            let last = last.ok_or_else(|| GhostError::InvalidInput("There is no prompt to run again".to_string()))?;
+            prompt = Some(last.content.clone());
+            drop(sessions);
+            show_window(window);
         }
-        Err(e) => {
-            let error_msg = format!("Failed to connect to proxy server: {}", e);
-            let _ = app_handle.emit("ai-response-error", &error_msg);
-            Err(error_msg)
+        HotkeyAction::StopGeneration => {
+            ACTIVE_STREAMS.cancel_all(true);
+        }
+        HotkeyAction::PasteLastAnswer => {
+            let mut sessions = SESSIONS.lock().unwrap();
+            let session_id = sessions.active_id().to_string();
+            let last = sessions.conversation(&session_id)?.last_message("assistant");
+            let answer = last.ok_or_else(|| GhostError::InvalidInput("There is no answer to paste".to_string()))?;
+            app_handle
+                .clipboard()
+                .write_text(answer.content.clone())
+                .map_err(|e| GhostError::Other(format!("Failed to copy the answer: {}", e)))?;
+            drop(sessions);
+
+            // Get out of the way so the paste lands in the app that had focus
+            // before us; hiding the whole app hands focus back on macOS
+            #[cfg(target_os = "macos")]
+            let _ = app_handle.hide();
+            #[cfg(not(target_os = "macos"))]
+            let _ = window.hide();
+            std::thread::sleep(Duration::from_millis(150));
+            paste::send_paste()?;
         }
     }
+
+    let _ = app_handle.emit("hotkey-action", HotkeyActionPayload { action, prompt });
+    Ok(())
 }
 
-async fn call_perplexity_api(
-    client: &Client,
-    model: &str,
-    prompt: &str,
-    app_handle: &tauri::AppHandle,
-) -> Result<(), String> {
-    // Use proxy server instead of direct API calls
-    let proxy_url = env::var("PROXY_URL")
-        .unwrap_or_else(|_| "https://proxy-server-p9wzc2v53-prem-thatikondas-projects.vercel.app".to_string());
-    
-    let url = format!("{}/api/perplexity", proxy_url);
-    
-    let request_body = serde_json::json!({
-        "model": model,
-        "prompt": prompt,
-        "stream": true
-    });
+#[tauri::command]
+fn get_settings() -> Result<Settings, GhostError> {
+    Ok(SETTINGS.lock().unwrap().clone())
+}
 
-    match client.post(&url).json(&request_body).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                let mut stream = response.bytes_stream();
-                let mut full_content = String::new();
-                
-                use futures_util::StreamExt;
-                
-                while let Some(chunk) = stream.next().await {
-                    let chunk = chunk.map_err(|e| format!("Stream error: {}", e))?;
-                    let chunk_str = String::from_utf8_lossy(&chunk);
-                    
-                    // Process each line in the chunk
-                    for line in chunk_str.lines() {
-                        if line.starts_with("data: ") {
-                            let data = &line[6..];
-                            if data == "[DONE]" {
-                                // Stream finished
-                                let mut conversation = CONVERSATION.lock().unwrap();
-                                conversation.add_message("assistant".to_string(), full_content.clone());
-                                let _ = app_handle.emit("ai-response-done", &full_content);
-                                return Ok(());
-                            }
-                            
-                            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(data) {
-                                if let Some(content) = parsed["content"].as_str() {
-                                    full_content.push_str(content);
-                                    let _ = app_handle.emit("ai-response-chunk", content);
-                                } else if let Some(error_msg) = parsed["error"].as_str() {
-                                    let _ = app_handle.emit("ai-response-error", error_msg);
-                                    return Err(format!("Proxy server error: {}", error_msg));
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                // If we get here, stream ended without [DONE]
-                let mut conversation = CONVERSATION.lock().unwrap();
-                conversation.add_message("assistant".to_string(), full_content.clone());
-                let _ = app_handle.emit("ai-response-done", &full_content);
-                Ok(())
-            } else {
-                let status = response.status();
-                let response_text = response.text().await.unwrap_or_else(|_| "Failed to read error response".to_string());
-                let error_msg = format!("Proxy server returned error: {} - {}", status, response_text);
-                let _ = app_handle.emit("ai-response-error", &error_msg);
-                Err(error_msg)
+// Runs on the main thread, where hotkeys have to be registered
+#[tauri::command]
+fn update_settings(settings: Settings, app_handle: tauri::AppHandle) -> Result<Settings, GhostError> {
+    change_settings(settings.clone(), &app_handle)?;
+    Ok(settings)
+}
+
+// Validate, persist and apply new settings, then tell the frontend with
+// `settings-changed`. Nothing changes if any step fails.
+fn change_settings(settings: Settings, app_handle: &tauri::AppHandle) -> Result<(), GhostError> {
+    // Checked before the rest so a conflict keeps its own kind
+    hotkeys::parse_bindings(&settings.hotkeys.bindings)?;
+    settings.validate().map_err(GhostError::InvalidInput)?;
+    // Also checks the proxy and CA certificates
+    let client = client::build(&settings.network).map_err(GhostError::InvalidInput)?;
+
+    let config_dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| GhostError::Storage(format!("Failed to locate the config directory: {}", e)))?;
+    settings.save(&config_dir).map_err(GhostError::Storage)?;
+
+    // Hotkeys can still fail, e.g. when another app holds the shortcut, so
+    // put the previous file back rather than keep settings that aren't in effect
+    let previous = SETTINGS.lock().unwrap().clone();
+    if settings.hotkeys.bindings != previous.hotkeys.bindings {
+        if let Err(error) = apply_hotkeys(&settings.hotkeys.bindings, app_handle) {
+            if let Err(e) = previous.save(&config_dir) {
+                log::error!("Failed to restore the previous settings: {}", e);
             }
+            return Err(error);
         }
-        Err(e) => {
-            let error_msg = format!("Failed to connect to proxy server: {}", e);
-            let _ = app_handle.emit("ai-response-error", &error_msg);
-            Err(error_msg)
+    }
+
+    app_handle.state::<SharedClient>().set(client);
+    apply_settings(&settings, app_handle);
+    *SETTINGS.lock().unwrap() = settings.clone();
+    let _ = app_handle.emit("settings-changed", settings);
+    Ok(())
+}
+
+// Push settings into the subsystems that keep their own copy. Hotkeys are
+// registered separately since that can fail.
+fn apply_settings(settings: &Settings, app_handle: &tauri::AppHandle) {
+    keys::configure(
+        settings.connection.mode,
+        settings.connection.key_storage,
+        settings.connection.env_api_keys,
+    );
+    SESSIONS.lock().unwrap().set_limits(&settings.context);
+    *PROVIDERS.lock().unwrap() = ProviderRegistry::from_settings(settings);
+    MODELS.lock().unwrap().invalidate();
+    tauri::async_runtime::spawn(discover_models(app_handle.state::<SharedClient>().get()));
+}
+
+// Models of every configured provider, cached for a few minutes unless `refresh` is set
+#[tauri::command]
+async fn list_models(refresh: Option<bool>, app_handle: tauri::AppHandle) -> Result<Vec<ModelInfo>, GhostError> {
+    if !refresh.unwrap_or(false) {
+        if let Some(models) = MODELS.lock().unwrap().get() {
+            return Ok(models);
         }
     }
+    Ok(discover_models(app_handle.state::<SharedClient>().get()).await)
 }
 
 #[tauri::command]
-fn get_conversation_history() -> Result<Vec<ConversationMessage>, String> {
-    let conversation = CONVERSATION.lock().unwrap();
-    Ok(conversation.messages.iter().cloned().collect())
+fn set_api_key(provider: String, api_key: String) -> Result<(), GhostError> {
+    keys::set_api_key(&provider, &api_key)?;
+    // Which models are listed depends on the keys
+    MODELS.lock().unwrap().invalidate();
+    Ok(())
 }
 
 #[tauri::command]
-fn clear_conversation() -> Result<(), String> {
-    let mut conversation = CONVERSATION.lock().unwrap();
-    conversation.clear();
+fn delete_api_key(provider: String) -> Result<(), GhostError> {
+    keys::delete_api_key(&provider)?;
+    MODELS.lock().unwrap().invalidate();
     Ok(())
 }
 
+// Providers that have an API key for direct mode
+#[tauri::command]
+fn list_configured_providers() -> Result<Vec<String>, GhostError> {
+    let names = PROVIDERS.lock().unwrap().names();
+    Ok(names.into_iter().filter(|name| keys::has_api_key(name)).collect())
+}
+
+#[tauri::command]
+fn unlock_vault(passphrase: String) -> Result<(), GhostError> {
+    keys::unlock_vault(&passphrase)
+}
+
 #[tauri::command]
-fn stop_streaming() -> Result<(), String> {
-    STREAM_CANCELLED.store(true, Ordering::Relaxed);
+fn lock_vault() -> Result<(), GhostError> {
+    keys::lock_vault();
+    Ok(())
+}
+
+#[tauri::command]
+fn create_session(name: Option<String>) -> Result<SessionInfo, GhostError> {
+    SESSIONS.lock().unwrap().create(name)
+}
+
+#[tauri::command]
+fn list_sessions() -> Result<Vec<SessionInfo>, GhostError> {
+    Ok(SESSIONS.lock().unwrap().list())
+}
+
+#[tauri::command]
+fn switch_session(session_id: String) -> Result<(), GhostError> {
+    SESSIONS.lock().unwrap().switch(&session_id)
+}
+
+#[tauri::command]
+fn rename_session(session_id: String, name: String) -> Result<SessionInfo, GhostError> {
+    SESSIONS.lock().unwrap().rename(&session_id, name)
+}
+
+#[tauri::command]
+fn delete_session(session_id: String) -> Result<(), GhostError> {
+    SESSIONS.lock().unwrap().delete(&session_id)
+}
+
+#[tauri::command]
+fn cancel_request(request_id: String, keep_partial: Option<bool>) -> Result<(), GhostError> {
+    if ACTIVE_STREAMS.cancel(&request_id, keep_partial.unwrap_or(true)) {
+        Ok(())
+    } else {
+        Err(GhostError::InvalidInput(format!("No active request with id {}", request_id)))
+    }
+}
+
+// Stops every in-flight generation
+// Each startup error is only returned once
+#[tauri::command]
+fn take_startup_errors(errors: tauri::State<StartupErrors>) -> Result<Vec<GhostError>, GhostError> {
+    Ok(std::mem::take(&mut *errors.0.lock().unwrap()))
+}
+
+#[tauri::command]
+fn stop_streaming(keep_partial: Option<bool>) -> Result<(), GhostError> {
+    ACTIVE_STREAMS.cancel_all(keep_partial.unwrap_or(true));
     Ok(())
 }
 
 fn main() {
-    // Load environment variables from .env file
-    dotenv().ok();
-    
-    // We need to create the hotkey manager before the app starts
-    let manager = GlobalHotKeyManager::new().unwrap();
-    
-    // Register our hotkey
-    let hotkey = HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::Space);
-    manager.register(hotkey).unwrap();
+    // We need to create the hotkey manager before the app starts, but can
+    // only log a failure once the log plugin is up
+    let hotkey_error = match HotkeyManager::new() {
+        Ok(manager) => {
+            *HOTKEYS.lock().unwrap() = Some(manager);
+            None
+        }
+        Err(e) => Some(e),
+    };
 
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![ask_ai_stream, get_conversation_history, clear_conversation, stop_streaming])
+        .plugin(tauri_plugin_log::Builder::new().level(log::LevelFilter::Info).build())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .invoke_handler(tauri::generate_handler![
+            ask_ai_stream,
+            get_conversation_history,
+            clear_conversation,
+            stop_streaming,
+            cancel_request,
+            create_session,
+            list_sessions,
+            switch_session,
+            rename_session,
+            delete_session,
+            list_personas,
+            create_persona,
+            update_persona,
+            delete_persona,
+            set_session_persona,
+            list_templates,
+            create_template,
+            update_template,
+            delete_template,
+            get_settings,
+            update_settings,
+            list_models,
+            set_api_key,
+            delete_api_key,
+            list_configured_providers,
+            unlock_vault,
+            lock_vault,
+            set_hotkey,
+            remove_hotkey,
+            take_startup_errors,
+        ])
         .setup(move |app| {
             // Get a handle to the main window
             let window = app.get_webview_window("main").unwrap();
             // Start the app hidden
             window.hide().unwrap();
 
+            // Restore persisted sessions, personas and templates, and locate the
+            // key vault. Any that can't be read are kept in memory for this run.
+            let data_dir = app.path().app_data_dir()?;
+            match SessionManager::open(&data_dir) {
+                Ok(sessions) => *SESSIONS.lock().unwrap() = sessions,
+                Err(e) => log::error!("Failed to load sessions: {}", e),
+            }
+            match PersonaManager::open(&data_dir) {
+                Ok(personas) => *PERSONAS.lock().unwrap() = personas,
+                Err(e) => log::error!("Failed to load personas: {}", e),
+            }
+            match TemplateManager::open(&data_dir) {
+                Ok(templates) => *TEMPLATES.lock().unwrap() = templates,
+                Err(e) => log::error!("Failed to load templates: {}", e),
+            }
+            keys::init(&data_dir);
+
+            // Load the settings file; a broken one shouldn't keep the app from starting
+            let mut startup_errors = Vec::new();
+            let settings = Settings::load(&app.path().app_config_dir()?).unwrap_or_else(|e| {
+                let error = GhostError::Storage(format!("{}; using the default settings", e));
+                log::warn!("{}", error);
+                startup_errors.push(error);
+                Settings::default()
+            });
+            // Without a working proxy or CA certificate, requests go out
+            // directly but keep the configured timeouts
+            let client = client::build(&settings.network).unwrap_or_else(|e| {
+                let error = GhostError::InvalidInput(format!(
+                    "{}; connecting without the configured proxy and CA certificates",
+                    e
+                ));
+                log::error!("{}", error);
+                startup_errors.push(error);
+                let network = NetworkSettings {
+                    proxy: None,
+                    ca_certificates: Vec::new(),
+                    ..settings.network.clone()
+                };
+                client::build(&network).unwrap_or_default()
+            });
+            app.manage(SharedClient::new(client));
+            app.manage(StartupErrors(Mutex::new(startup_errors)));
+            // Also fills the model catalog, including locally available models
+            apply_settings(&settings, app.handle());
+            *SETTINGS.lock().unwrap() = settings;
+
+            // Register our hotkeys; if that fails the app still runs, just without them
+            if let Some(e) = &hotkey_error {
+                log::error!("{}", e);
+            }
+            let bindings = SETTINGS.lock().unwrap().hotkeys.bindings.clone();
+            if let Err(e) = apply_hotkeys(&bindings, app.handle()) {
+                log::error!("{}", e);
+            }
+
             // --- macOS Specific: Hide Dock icon and make it a utility panel ---
             #[cfg(target_os = "macos")]
             {
@@ -395,34 +983,30 @@ fn main() {
                 }
             }
             
-            // --- This thread listens for the hotkey press ---
+            // --- This thread listens for hotkey presses ---
             let main_window = window.clone();
             std::thread::spawn(move || {
                 let event_receiver = GlobalHotKeyEvent::receiver();
-                let mut last_toggle = std::time::Instant::now();
-                
+                let mut last_action = std::time::Instant::now();
+
                 for event in event_receiver.iter() {
-                    if event.id == hotkey.id() {
-                        // Debounce: only allow toggling every 200ms
-                        if last_toggle.elapsed().as_millis() < 200 {
-                            continue;
-                        }
-                        last_toggle = std::time::Instant::now();
-                        
-                        // Toggle window visibility
-                        if main_window.is_visible().unwrap() {
-                            main_window.hide().unwrap();
-                        } else {
-                            // Center the window on screen when showing
-                            main_window.center().unwrap();
-                            main_window.show().unwrap();
-                            main_window.set_focus().unwrap();
-                            // Ensure the window stays on top and focused
-                            main_window.set_always_on_top(true).unwrap();
-                            main_window.unminimize().unwrap();
-                            // Bring to front
-                            main_window.set_always_on_top(false).unwrap(); // Reset to allow normal window behavior
-                        }
+                    if event.state != HotKeyState::Pressed {
+                        continue;
+                    }
+                    let action = HOTKEYS.lock().unwrap().as_ref().and_then(|hotkeys| hotkeys.action(event.id));
+                    let Some(action) = action else {
+                        continue;
+                    };
+
+                    // Debounce: ignore presses that come too quickly after the last one
+                    let debounce = SETTINGS.lock().unwrap().hotkeys.debounce_ms;
+                    if last_action.elapsed().as_millis() < u128::from(debounce) {
+                        continue;
+                    }
+                    last_action = std::time::Instant::now();
+
+                    if let Err(error) = run_hotkey_action(action, &main_window) {
+                        let _ = main_window.emit("hotkey-error", HotkeyErrorPayload { error });
                     }
                 }
             });
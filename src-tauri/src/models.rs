@@ -0,0 +1,77 @@
+// Catalog of every model the configured providers can serve, so the model
+// picker always matches what `ask_ai_stream` accepts. Discovery hits the
+// network for some providers, so the result is cached for a few minutes.
+
+use crate::context::DEFAULT_CONTEXT_WINDOW;
+use crate::keys;
+use crate::providers::{ModelInfo, Provider};
+use futures_util::future;
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const MAX_AGE: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Default)]
+pub struct ModelCatalog {
+    models: Vec<ModelInfo>,
+    refreshed_at: Option<Instant>,
+}
+
+impl ModelCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached models, or None if they are missing or out of date.
+    pub fn get(&self) -> Option<Vec<ModelInfo>> {
+        let refreshed_at = self.refreshed_at?;
+        (refreshed_at.elapsed() < MAX_AGE).then(|| self.models.clone())
+    }
+
+    pub fn set(&mut self, models: Vec<ModelInfo>) {
+        self.models = models;
+        self.refreshed_at = Some(Instant::now());
+    }
+
+    /// Context length of `model` as its provider reported it. Stale entries
+    /// still count, since context lengths rarely change.
+    pub fn context_window(&self, model: &str) -> usize {
+        self.models
+            .iter()
+            .find(|m| m.id == model)
+            .and_then(|m| m.context_length)
+            .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+    }
+
+    /// Force the next `get` to miss, e.g. after the providers changed.
+    pub fn invalidate(&mut self) {
+        self.refreshed_at = None;
+    }
+}
+
+/// Ask every provider for its models at once. A provider that can't be
+/// reached falls back to the models it knows about without asking. Providers
+/// missing the API key they need are skipped, since none of their models
+/// could answer.
+pub async fn discover(providers: Vec<Arc<dyn Provider>>, client: &Client) -> Vec<ModelInfo> {
+    let providers: Vec<_> = providers
+        .into_iter()
+        .filter(|p| !p.needs_api_key() || keys::has_api_key(p.name()))
+        .collect();
+    let results = future::join_all(providers.iter().map(|p| p.discover_models(client))).await;
+
+    providers
+        .iter()
+        .zip(results)
+        .flat_map(|(provider, result)| {
+            result.unwrap_or_else(|_| {
+                provider
+                    .supported_models()
+                    .into_iter()
+                    .map(|id| ModelInfo::new(id, provider.name()))
+                    .collect()
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,35 @@
+// Parser for newline-delimited JSON bodies (Ollama's streaming format).
+// Splitting the body into lines is left to `lines`; this only drops the
+// blank lines between objects.
+
+use crate::lines::LineParser;
+
+#[derive(Debug, Default)]
+pub struct NdjsonParser;
+
+impl LineParser for NdjsonParser {
+    type Item = String;
+
+    fn parse_line(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        (!line.is_empty()).then(|| line.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::LineDecoder;
+
+    #[test]
+    fn lines_split_across_chunks() {
+        let input = "{\"a\":\"é\"}\n{\"b\":2}\r\n\n{\"c\":3}".as_bytes();
+        for split in 1..input.len() {
+            let mut decoder = LineDecoder::new(NdjsonParser);
+            let mut lines = decoder.feed(&input[..split]);
+            lines.extend(decoder.feed(&input[split..]));
+            lines.extend(decoder.finish());
+            assert_eq!(lines, vec!["{\"a\":\"é\"}", "{\"b\":2}", "{\"c\":3}"], "split at {}", split);
+        }
+    }
+}
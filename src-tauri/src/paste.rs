@@ -0,0 +1,84 @@
+// Sends the platform's paste shortcut (Cmd+V on macOS, Ctrl+V elsewhere) to
+// whichever application has focus, for the "paste last answer" hotkey.
+// Modifiers the user may still be holding from the hotkey are kept out of
+// the synthesized keystroke.
+
+use crate::error::GhostError;
+
+/// Paste the clipboard into the focused application. On macOS this needs the
+/// Accessibility permission; without it the keystroke is silently dropped.
+#[cfg(target_os = "macos")]
+pub fn send_paste() -> Result<(), GhostError> {
+    use core_graphics::event::{CGEvent, CGEventFlags, CGEventTapLocation};
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+    // Virtual key code of "V" on an ANSI keyboard
+    const KEY_V: u16 = 9;
+
+    let failed = |_| GhostError::Other("Failed to create the paste keystroke".to_string());
+    let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState).map_err(failed)?;
+    for key_down in [true, false] {
+        let event = CGEvent::new_keyboard_event(source.clone(), KEY_V, key_down).map_err(failed)?;
+        // Explicit flags replace whatever modifiers are physically held
+        event.set_flags(CGEventFlags::CGEventFlagCommand);
+        event.post(CGEventTapLocation::HID);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn send_paste() -> Result<(), GhostError> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        GetAsyncKeyState, SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP,
+        VIRTUAL_KEY, VK_CONTROL, VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT, VK_V,
+    };
+
+    fn key(key: VIRTUAL_KEY, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: key,
+                    wScan: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        }
+    }
+
+    // Let go of modifiers still held from the hotkey, or they'd join the Ctrl+V
+    let mut inputs = Vec::new();
+    for modifier in [VK_SHIFT, VK_MENU, VK_LWIN, VK_RWIN] {
+        if unsafe { GetAsyncKeyState(i32::from(modifier.0)) } < 0 {
+            inputs.push(key(modifier, KEYEVENTF_KEYUP));
+        }
+    }
+    inputs.extend([
+        key(VK_CONTROL, KEYBD_EVENT_FLAGS(0)),
+        key(VK_V, KEYBD_EVENT_FLAGS(0)),
+        key(VK_V, KEYEVENTF_KEYUP),
+        key(VK_CONTROL, KEYEVENTF_KEYUP),
+    ]);
+
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        return Err(GhostError::Other("Failed to send the paste keystroke".to_string()));
+    }
+    Ok(())
+}
+
+// There's no portable way to send keys on Linux; xdotool covers X11 and
+// XWayland applications
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn send_paste() -> Result<(), GhostError> {
+    let status = std::process::Command::new("xdotool")
+        .args(["key", "--clearmodifiers", "ctrl+v"])
+        .status()
+        .map_err(|e| GhostError::Other(format!("Pasting needs xdotool: {}", e)))?;
+    if !status.success() {
+        return Err(GhostError::Other(format!("xdotool failed to paste ({})", status)));
+    }
+    Ok(())
+}
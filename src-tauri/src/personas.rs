@@ -0,0 +1,56 @@
+// Reusable personas: a system prompt plus optional default model and
+// generation options. Sessions refer to a persona by ID; personas are kept in
+// `personas.json`.
+
+use crate::generation::GenerationOptions;
+use crate::json_store::{JsonStore, Record};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Persona {
+    pub id: String,
+    pub name: String,
+    pub system_prompt: String,
+    /// Model used when a request doesn't name one.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Applied on top of the configured defaults for the model.
+    #[serde(default)]
+    pub options: GenerationOptions,
+}
+
+/// Personas, kept in `personas.json`. Sessions that still refer to a deleted
+/// persona fall back to no persona.
+pub type PersonaManager = JsonStore<Persona>;
+
+impl Persona {
+    pub fn new(name: String, system_prompt: String, model: Option<String>, options: GenerationOptions) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            system_prompt,
+            model,
+            options,
+        }
+    }
+}
+
+impl Record for Persona {
+    const KIND: &'static str = "persona";
+    const FILE: &'static str = "personas.json";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Persona name must not be empty".to_string());
+        }
+        if self.system_prompt.trim().is_empty() {
+            return Err("System prompt must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+// Anthropic Messages API. Unlike the other backends, every SSE event carries
+// its type in the `event:` field (`message_start`, `content_block_delta`,
+// `message_delta`, `message_stop`, `error`, `ping`), so parsing dispatches on
+// that rather than on the shape of `data:`.
+
+use super::{fail, http, ChatMessage, ChatRequest, EventStream, ModelInfo, Provider, StopReason, StreamEvent, Usage};
+use crate::error::GhostError;
+use crate::generation::OptionLimits;
+use crate::keys;
+use crate::sse::SseEvent;
+use futures_util::future::{self, BoxFuture};
+use futures_util::FutureExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+const API_URL: &str = "https://api.anthropic.com/v1/messages";
+const API_VERSION: &str = "2023-06-01";
+// The Messages API requires a limit
+const DEFAULT_MAX_TOKENS: u32 = 2048;
+
+#[derive(Debug, Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<&'a str>,
+    messages: &'a [ChatMessage],
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop_sequences: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageStart {
+    message: StartedMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct StartedMessage {
+    #[serde(default)]
+    usage: Usage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlockDelta {
+    delta: BlockDelta,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum BlockDelta {
+    #[serde(rename = "text_delta")]
+    Text { text: String },
+    // Tool input and thinking deltas aren't shown
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDelta {
+    delta: MessageDeltaBody,
+    usage: Option<DeltaUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDeltaBody {
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaUsage {
+    output_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorEvent {
+    error: ErrorBody,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+pub struct AnthropicProvider;
+
+impl Provider for AnthropicProvider {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn supported_models(&self) -> Vec<String> {
+        known_models().into_iter().map(|m| m.id).collect()
+    }
+
+    fn option_limits(&self) -> OptionLimits {
+        OptionLimits {
+            max_temperature: 1.0,
+            max_stop_sequences: 8,
+            seed: false,
+        }
+    }
+
+    // In either connection mode
+    fn needs_api_key(&self) -> bool {
+        true
+    }
+
+    fn discover_models(&self, _client: &Client) -> BoxFuture<'static, Result<Vec<ModelInfo>, GhostError>> {
+        future::ready(Ok(known_models())).boxed()
+    }
+
+    fn supports(&self, model: &str) -> bool {
+        model.starts_with("claude-")
+    }
+
+    // Always direct: the proxy server has no Anthropic endpoint
+    fn stream(&self, client: &Client, request: ChatRequest) -> EventStream {
+        let api_key = match keys::api_key(self.name()) {
+            Ok(api_key) => api_key,
+            Err(e) => return fail(e),
+        };
+
+        let body = MessagesRequest {
+            model: &request.model,
+            max_tokens: request.options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            system: request.system.as_deref(),
+            messages: &request.messages,
+            stream: true,
+            temperature: request.options.temperature,
+            top_p: request.options.top_p,
+            stop_sequences: &request.options.stop,
+        };
+        let builder = client
+            .post(API_URL)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&body);
+
+        let mut parser = EventParser::default();
+        http::stream_sse(builder, "Anthropic API", move |event| parser.parse(event))
+    }
+}
+
+fn known_models() -> Vec<ModelInfo> {
+    let model = |id: &str, name: &str, description: &str| ModelInfo {
+        name: name.to_string(),
+        description: description.to_string(),
+        context_length: Some(200_000),
+        vision: true,
+        tools: true,
+        ..ModelInfo::new(id, "anthropic")
+    };
+
+    vec![
+        model("claude-sonnet-4-5", "Claude Sonnet 4.5", "Balanced speed and intelligence"),
+        model("claude-haiku-4-5", "Claude Haiku 4.5", "Fastest Claude model"),
+        model("claude-opus-4-1", "Claude Opus 4.1", "Most capable Claude model"),
+    ]
+}
+
+#[derive(Debug, Default)]
+struct EventParser {
+    // Input tokens are only reported in `message_start`
+    usage: Usage,
+}
+
+impl EventParser {
+    fn parse(&mut self, event: &SseEvent) -> Option<Result<StreamEvent, GhostError>> {
+        match event.event.as_str() {
+            "message_start" => {
+                let start: MessageStart = serde_json::from_str(&event.data).ok()?;
+                self.usage = start.message.usage;
+                None
+            }
+            "content_block_delta" => {
+                let delta: ContentBlockDelta = serde_json::from_str(&event.data).ok()?;
+                match delta.delta {
+                    BlockDelta::Text { text } if !text.is_empty() => Some(Ok(StreamEvent::Delta(text))),
+                    _ => None,
+                }
+            }
+            "message_delta" => {
+                let delta: MessageDelta = serde_json::from_str(&event.data).ok()?;
+                if let Some(usage) = delta.usage {
+                    self.usage.output_tokens = usage.output_tokens;
+                }
+                let reason = match delta.delta.stop_reason?.as_str() {
+                    "end_turn" => StopReason::EndTurn,
+                    "max_tokens" => StopReason::MaxTokens,
+                    "stop_sequence" => StopReason::StopSequence,
+                    _ => StopReason::Other,
+                };
+                Some(Ok(StreamEvent::Stop {
+                    reason,
+                    usage: Some(self.usage),
+                }))
+            }
+            "message_stop" => Some(Ok(StreamEvent::Done)),
+            "error" => {
+                let service = "Anthropic API".to_string();
+                let Ok(ErrorEvent { error }) = serde_json::from_str(&event.data) else {
+                    return Some(Err(GhostError::ProviderError {
+                        service,
+                        code: None,
+                        message: event.data.clone(),
+                    }));
+                };
+                Some(Err(match error.kind.as_str() {
+                    "rate_limit_error" => GhostError::RateLimited {
+                        service,
+                        retry_after: None,
+                    },
+                    "authentication_error" | "permission_error" => GhostError::Auth {
+                        service,
+                        message: error.message,
+                    },
+                    _ => GhostError::ProviderError {
+                        service,
+                        code: None,
+                        message: error.message,
+                    },
+                }))
+            }
+            // `ping` and `content_block_start`/`content_block_stop`
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: &str, data: &str) -> SseEvent {
+        SseEvent {
+            event: event.to_string(),
+            data: data.to_string(),
+            id: None,
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn maps_message_events() {
+        let mut parser = EventParser::default();
+        let events: Vec<_> = [
+            event("message_start", r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":25,"output_tokens":1}}}"#),
+            event("content_block_start", r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#),
+            event("ping", r#"{"type":"ping"}"#),
+            event("content_block_delta", r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#),
+            event("content_block_delta", r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{"}}"#),
+            event("content_block_stop", r#"{"type":"content_block_stop","index":0}"#),
+            event("message_delta", r#"{"type":"message_delta","delta":{"stop_reason":"max_tokens","stop_sequence":null},"usage":{"output_tokens":15}}"#),
+            event("message_stop", r#"{"type":"message_stop"}"#),
+        ]
+        .iter()
+        .filter_map(|e| parser.parse(e))
+        .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                Ok(StreamEvent::Delta("Hello".to_string())),
+                Ok(StreamEvent::Stop {
+                    reason: StopReason::MaxTokens,
+                    usage: Some(Usage {
+                        input_tokens: 25,
+                        output_tokens: 15,
+                    }),
+                }),
+                Ok(StreamEvent::Done),
+            ]
+        );
+    }
+
+    #[test]
+    fn error_event_fails_the_stream() {
+        let mut parser = EventParser::default();
+        let error = event("error", r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#);
+        assert_eq!(
+            parser.parse(&error),
+            Some(Err(GhostError::ProviderError {
+                service: "Anthropic API".to_string(),
+                code: None,
+                message: "Overloaded".to_string(),
+            }))
+        );
+
+        let error = event("error", r#"{"type":"error","error":{"type":"rate_limit_error","message":"Slow down"}}"#);
+        assert!(matches!(parser.parse(&error), Some(Err(GhostError::RateLimited { .. }))));
+    }
+}
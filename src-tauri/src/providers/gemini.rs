@@ -0,0 +1,198 @@
+use super::{fail, http, proxy, ChatRequest, EventStream, ModelInfo, Provider, Role, StreamEvent};
+use crate::error::GhostError;
+use crate::generation::OptionLimits;
+use crate::keys;
+use crate::settings::{ConnectionMode, ConnectionSettings};
+use crate::sse::SseEvent;
+use futures_util::future::{self, BoxFuture};
+use futures_util::FutureExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+const API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+// Gemini API structures, used in direct mode
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    contents: Vec<GeminiContent>,
+    generation_config: GeminiGenerationConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiPart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiCandidate {
+    content: Option<GeminiContent>,
+}
+
+pub struct GeminiProvider {
+    mode: ConnectionMode,
+    proxy_url: String,
+}
+
+impl GeminiProvider {
+    pub fn new(connection: &ConnectionSettings) -> Self {
+        Self {
+            mode: connection.mode,
+            proxy_url: connection.proxy_url.clone(),
+        }
+    }
+
+    fn stream_direct(&self, client: &Client, request: ChatRequest) -> EventStream {
+        let api_key = match keys::api_key(self.name()) {
+            Ok(api_key) => api_key,
+            Err(e) => return fail(e),
+        };
+
+        let body = GeminiRequest {
+            system_instruction: request.system.as_ref().map(|system| GeminiContent {
+                role: None,
+                parts: vec![GeminiPart { text: system.clone() }],
+            }),
+            contents: request
+                .messages
+                .iter()
+                .map(|m| GeminiContent {
+                    role: Some(match m.role {
+                        Role::User | Role::System => "user".to_string(),
+                        Role::Assistant => "model".to_string(),
+                    }),
+                    parts: vec![GeminiPart { text: m.content.clone() }],
+                })
+                .collect(),
+            generation_config: GeminiGenerationConfig {
+                temperature: request.options.temperature,
+                top_p: request.options.top_p,
+                max_output_tokens: request.options.max_tokens,
+                stop_sequences: request.options.stop.clone(),
+                seed: request.options.seed,
+            },
+        };
+
+        let url = format!("{}/models/{}:streamGenerateContent?alt=sse", API_BASE, request.model);
+        let builder = client.post(url).header("x-goog-api-key", api_key).json(&body);
+        http::stream_sse(builder, "Gemini API", parse_event)
+    }
+}
+
+impl Provider for GeminiProvider {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    fn supported_models(&self) -> Vec<String> {
+        known_models().into_iter().map(|m| m.id).collect()
+    }
+
+    fn needs_api_key(&self) -> bool {
+        self.mode == ConnectionMode::Direct
+    }
+
+    fn discover_models(&self, client: &Client) -> BoxFuture<'static, Result<Vec<ModelInfo>, GhostError>> {
+        match self.mode {
+            ConnectionMode::Proxy => proxy::models(client, &self.proxy_url, "gemini"),
+            ConnectionMode::Direct => future::ready(Ok(known_models())).boxed(),
+        }
+    }
+
+    fn supports(&self, model: &str) -> bool {
+        model.starts_with("gemini")
+    }
+
+    fn option_limits(&self) -> OptionLimits {
+        OptionLimits {
+            max_stop_sequences: 5,
+            ..OptionLimits::default()
+        }
+    }
+
+    fn stream(&self, client: &Client, request: ChatRequest) -> EventStream {
+        if self.mode == ConnectionMode::Direct {
+            return self.stream_direct(client, request);
+        }
+
+        proxy::stream(client, &self.proxy_url, "gemini", proxy::chat_body(&request))
+    }
+}
+
+fn known_models() -> Vec<ModelInfo> {
+    let model = |id: &str, name: &str, description: &str, context_length: usize| ModelInfo {
+        name: name.to_string(),
+        description: description.to_string(),
+        context_length: Some(context_length),
+        vision: true,
+        tools: true,
+        ..ModelInfo::new(id, "gemini")
+    };
+
+    vec![
+        model("gemini-2.0-flash", "Gemini 2.0 Flash", "Advanced Gemini model for fast responses", 1_048_576),
+        model("gemini-1.5-flash", "Gemini 1.5 Flash", "Fast and efficient for quick responses", 1_048_576),
+        model("gemini-1.5-pro", "Gemini 1.5 Pro", "Most capable model for complex tasks", 2_097_152),
+    ]
+}
+
+// Gemini ends the stream by closing the connection rather than sending [DONE]
+fn parse_event(event: &SseEvent) -> Option<Result<StreamEvent, GhostError>> {
+    let value: serde_json::Value = serde_json::from_str(&event.data).ok()?;
+    if let Some(error) = value.get("error") {
+        let message = error["message"].as_str().unwrap_or("Unknown error");
+        return Some(Err(GhostError::ProviderError {
+            service: "Gemini API".to_string(),
+            code: error["code"].as_u64().map(|code| code as u16),
+            message: message.to_string(),
+        }));
+    }
+
+    let response: GeminiResponse = serde_json::from_value(value).ok()?;
+    let text: String = response
+        .candidates
+        .into_iter()
+        .next()?
+        .content?
+        .parts
+        .into_iter()
+        .map(|p| p.text)
+        .collect();
+
+    if text.is_empty() {
+        return None;
+    }
+    Some(Ok(StreamEvent::Delta(text)))
+}
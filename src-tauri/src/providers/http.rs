@@ -0,0 +1,80 @@
+// Shared request/response handling for providers that stream over HTTP.
+
+use super::{EventStream, StreamEvent};
+use crate::error::GhostError;
+use crate::lines::{self, LineParser};
+use crate::ndjson::NdjsonParser;
+use crate::sse::{SseEvent, SseParser};
+use futures_util::{future, stream, StreamExt};
+use reqwest::{RequestBuilder, Response};
+
+/// Send `request`, turning connection failures and non-success statuses into
+/// errors. `service` names the other end in error messages.
+pub async fn send(request: RequestBuilder, service: &str) -> Result<Response, GhostError> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| GhostError::from_reqwest(service, e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let response_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Failed to read error response".to_string());
+        return Err(GhostError::from_status(&capitalize(service), status, &headers, response_text));
+    }
+
+    Ok(response)
+}
+
+/// Send `request` and turn the SSE reply into provider events. `parse` maps
+/// each SSE event to a provider event, or None to skip it.
+pub fn stream_sse<F>(request: RequestBuilder, service: impl Into<String>, mut parse: F) -> EventStream
+where
+    F: FnMut(&SseEvent) -> Option<Result<StreamEvent, GhostError>> + Send + 'static,
+{
+    stream_lines(request, service.into(), SseParser::default(), move |event| parse(&event))
+}
+
+/// Like `stream_sse`, for bodies of newline-delimited JSON. `parse` gets each
+/// line as it arrives.
+pub fn stream_ndjson<F>(request: RequestBuilder, service: impl Into<String>, mut parse: F) -> EventStream
+where
+    F: FnMut(&str) -> Option<Result<StreamEvent, GhostError>> + Send + 'static,
+{
+    stream_lines(request, service.into(), NdjsonParser, move |line| parse(&line))
+}
+
+// Send `request` and map whatever `parser` makes of the body's lines to provider events
+fn stream_lines<P, F>(request: RequestBuilder, service: String, parser: P, mut parse: F) -> EventStream
+where
+    P: LineParser + Send + 'static,
+    P::Item: Send,
+    F: FnMut(P::Item) -> Option<Result<StreamEvent, GhostError>> + Send + 'static,
+{
+    let events = stream::once(async move {
+        match send(request, &service).await {
+            Ok(response) => lines::decode(response.bytes_stream(), parser)
+                .map(move |item| match item {
+                    Ok(item) => parse(item),
+                    Err(e) => Some(Err(GhostError::from_body(&service, e))),
+                })
+                .filter_map(future::ready)
+                .boxed(),
+            Err(e) => stream::iter(vec![Err(e)]).boxed(),
+        }
+    })
+    .flatten();
+
+    Box::pin(events)
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,225 @@
+// Pluggable AI backends. Each provider turns a `ChatRequest` into a stream of
+// `StreamEvent`s; `ask_ai_stream` only deals with the stream and never with
+// provider-specific wire formats.
+
+mod anthropic;
+mod gemini;
+mod http;
+mod ollama;
+mod openai;
+mod perplexity;
+mod proxy;
+
+use crate::error::GhostError;
+use crate::generation::{GenerationOptions, OptionLimits};
+use crate::settings::Settings;
+use futures_util::future::{self, BoxFuture};
+use futures_util::{stream, FutureExt, Stream};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub use anthropic::AnthropicProvider;
+pub use gemini::GeminiProvider;
+pub use ollama::OllamaProvider;
+pub use openai::OpenAiCompatibleProvider;
+pub use perplexity::PerplexityProvider;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub model: String,
+    /// Instructions from the session's persona, kept out of `messages`.
+    pub system: Option<String>,
+    /// Conversation turns, oldest first, ending with the user's new message.
+    pub messages: Vec<ChatMessage>,
+    /// Already validated against the provider's `option_limits`.
+    pub options: GenerationOptions,
+}
+
+impl ChatRequest {
+    /// Text of the latest user turn.
+    pub fn prompt(&self) -> &str {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::User)
+            .map(|m| m.content.as_str())
+            .unwrap_or_default()
+    }
+
+    /// `messages` preceded by the system prompt as a system turn, for APIs
+    /// that take it in-band.
+    pub fn messages_with_system(&self) -> Vec<ChatMessage> {
+        let system = self.system.iter().map(|content| ChatMessage {
+            role: Role::System,
+            content: content.clone(),
+        });
+        system.chain(self.messages.iter().cloned()).collect()
+    }
+}
+
+/// Why the model stopped generating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    EndTurn,
+    /// The answer hit the output token limit and is cut short.
+    MaxTokens,
+    StopSequence,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// A piece of generated text.
+    Delta(String),
+    /// Generation stopped, sent by providers that report why. Token usage is
+    /// included when the provider reports it.
+    Stop { reason: StopReason, usage: Option<Usage> },
+    /// The provider signalled the end of the answer.
+    Done,
+}
+
+/// A model as shown in the model picker.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub name: String,
+    /// Name of the provider that serves it.
+    pub provider: String,
+    pub description: String,
+    /// Prompt and output tokens combined, if known.
+    pub context_length: Option<usize>,
+    /// Accepts images as input.
+    pub vision: bool,
+    /// Supports tool / function calling.
+    pub tools: bool,
+}
+
+impl ModelInfo {
+    /// Entry for a model we only know the ID of.
+    pub fn new(id: impl Into<String>, provider: &str) -> Self {
+        let id = id.into();
+        Self {
+            name: id.clone(),
+            id,
+            provider: provider.to_string(),
+            description: String::new(),
+            context_length: None,
+            vision: false,
+            tools: false,
+        }
+    }
+}
+
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, GhostError>> + Send>>;
+
+pub trait Provider: Send + Sync {
+    /// Short identifier, e.g. "gemini".
+    fn name(&self) -> &str;
+
+    /// IDs of the models this provider is known to serve.
+    fn supported_models(&self) -> Vec<String>;
+
+    /// Whether this provider can serve `model`. Defaults to an exact match
+    /// against `supported_models`.
+    fn supports(&self, model: &str) -> bool {
+        self.supported_models().iter().any(|m| m == model)
+    }
+
+    /// Whether requests need an API key from `keys`. Providers that need one
+    /// but have none are left out of model discovery.
+    fn needs_api_key(&self) -> bool {
+        false
+    }
+
+    /// Ranges of generation options this provider accepts.
+    fn option_limits(&self) -> OptionLimits {
+        OptionLimits::default()
+    }
+
+    /// Ask the backend which models it currently serves, with whatever it
+    /// reports about their capabilities. Defaults to `supported_models`.
+    fn discover_models(&self, _client: &Client) -> BoxFuture<'static, Result<Vec<ModelInfo>, GhostError>> {
+        let models = self
+            .supported_models()
+            .into_iter()
+            .map(|id| ModelInfo::new(id, self.name()))
+            .collect();
+        future::ready(Ok(models)).boxed()
+    }
+
+    /// Start generating. Nothing is sent until the returned stream is polled,
+    /// and dropping the stream aborts the underlying HTTP request.
+    fn stream(&self, client: &Client, request: ChatRequest) -> EventStream;
+}
+
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn Provider>>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self { providers: Vec::new() }
+    }
+
+    /// Registry with every built-in provider, configured from `settings`.
+    pub fn from_settings(settings: &Settings) -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(GeminiProvider::new(&settings.connection)));
+        registry.register(Arc::new(PerplexityProvider::new(&settings.connection)));
+        registry.register(Arc::new(AnthropicProvider));
+        for config in &settings.openai_compatible {
+            registry.register(Arc::new(OpenAiCompatibleProvider::new(config.clone())));
+        }
+        if settings.ollama.enabled {
+            registry.register(Arc::new(OllamaProvider::new(&settings.ollama, &settings.context)));
+        }
+        registry
+    }
+
+    /// Add `provider`, replacing any existing provider with the same name.
+    pub fn register(&mut self, provider: Arc<dyn Provider>) {
+        self.providers.retain(|p| p.name() != provider.name());
+        self.providers.push(provider);
+    }
+
+    pub fn all(&self) -> Vec<Arc<dyn Provider>> {
+        self.providers.clone()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.providers.iter().map(|p| p.name().to_string()).collect()
+    }
+
+    /// First registered provider that supports `model`.
+    pub fn find(&self, model: &str) -> Option<Arc<dyn Provider>> {
+        self.providers.iter().find(|p| p.supports(model)).cloned()
+    }
+}
+
+/// Stream that fails straight away, e.g. because no API key is available.
+fn fail(error: GhostError) -> EventStream {
+    Box::pin(stream::iter(vec![Err(error)]))
+}
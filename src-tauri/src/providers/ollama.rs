@@ -0,0 +1,253 @@
+// Local models served by Ollama. Requests never leave the machine, so this
+// works offline and ignores the connection mode.
+
+use super::{http, ChatMessage, ChatRequest, EventStream, ModelInfo, Provider, StreamEvent};
+use crate::context::DEFAULT_CONTEXT_WINDOW;
+use crate::error::GhostError;
+use crate::generation::GenerationOptions;
+use crate::settings::{ContextSettings, OllamaSettings};
+use futures_util::future::{self, BoxFuture};
+use futures_util::FutureExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    options: OllamaOptions<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    num_ctx: usize,
+}
+
+impl<'a> OllamaOptions<'a> {
+    fn new(options: &'a GenerationOptions, num_ctx: usize) -> Self {
+        Self {
+            temperature: options.temperature,
+            top_p: options.top_p,
+            num_predict: options.max_tokens,
+            stop: &options.stop,
+            seed: options.seed,
+            num_ctx,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChatChunk {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaTags {
+    #[serde(default)]
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModel {
+    name: String,
+    #[serde(default)]
+    details: OllamaModelDetails,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct OllamaModelDetails {
+    #[serde(default)]
+    families: Option<Vec<String>>,
+    #[serde(default)]
+    parameter_size: String,
+}
+
+// Reply of `/api/show`. `model_info` is keyed by architecture, e.g.
+// "llama.context_length".
+#[derive(Debug, Deserialize)]
+struct OllamaShow {
+    #[serde(default)]
+    model_info: HashMap<String, serde_json::Value>,
+}
+
+pub struct OllamaProvider {
+    base_url: String,
+    context: ContextSettings,
+    // Filled in by `discover_models`; empty until Ollama has answered once
+    models: Arc<Mutex<Vec<String>>>,
+    context_lengths: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl OllamaProvider {
+    pub fn new(settings: &OllamaSettings, context: &ContextSettings) -> Self {
+        Self {
+            base_url: settings.base_url.trim_end_matches('/').to_string(),
+            context: context.clone(),
+            models: Arc::new(Mutex::new(Vec::new())),
+            context_lengths: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Ollama only gives a model the context it's asked for (a few thousand
+    // tokens by default) and cuts longer prompts from the front. Ask for room
+    // for the history budget and the answer. The size only depends on the
+    // settings, since a change makes Ollama reload the model.
+    fn num_ctx(&self, request: &ChatRequest) -> usize {
+        let lengths = self.context_lengths.lock().unwrap();
+        let window = lengths
+            .get(&request.model)
+            .or_else(|| lengths.get(&format!("{}:latest", request.model)))
+            .copied()
+            .unwrap_or(DEFAULT_CONTEXT_WINDOW);
+        let output = request
+            .options
+            .max_tokens
+            .map_or(self.context.reserve_output_tokens, |tokens| tokens as usize);
+        (self.context.budget_for(window) + output).min(window)
+    }
+}
+
+impl Provider for OllamaProvider {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn supported_models(&self) -> Vec<String> {
+        self.models.lock().unwrap().clone()
+    }
+
+    fn supports(&self, model: &str) -> bool {
+        // "llama3.2" and "llama3.2:latest" name the same model
+        let models = self.models.lock().unwrap();
+        models
+            .iter()
+            .any(|m| m == model || m.strip_suffix(":latest") == Some(model))
+    }
+
+    fn discover_models(&self, client: &Client) -> BoxFuture<'static, Result<Vec<ModelInfo>, GhostError>> {
+        let request = client.get(format!("{}/api/tags", self.base_url));
+        let client = client.clone();
+        let show_url = format!("{}/api/show", self.base_url);
+        let models = self.models.clone();
+        let context_lengths = self.context_lengths.clone();
+
+        async move {
+            let response = http::send(request, "Ollama").await?;
+            let tags: OllamaTags = response
+                .json()
+                .await
+                .map_err(|e| GhostError::ProviderError {
+                    service: "Ollama".to_string(),
+                    code: None,
+                    message: format!("unreadable model list ({})", e),
+                })?;
+
+            // The listing leaves out context lengths; those take a request per model
+            let shown = tags
+                .models
+                .iter()
+                .map(|m| context_length(&client, &show_url, &m.name));
+            let lengths = future::join_all(shown).await;
+
+            *models.lock().unwrap() = tags.models.iter().map(|m| m.name.clone()).collect();
+            *context_lengths.lock().unwrap() = tags
+                .models
+                .iter()
+                .zip(&lengths)
+                .filter_map(|(m, length)| Some((m.name.clone(), (*length)?)))
+                .collect();
+
+            Ok(tags
+                .models
+                .into_iter()
+                .zip(lengths)
+                .map(|(m, length)| ModelInfo {
+                    context_length: length,
+                    ..model_info(m)
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn stream(&self, client: &Client, request: ChatRequest) -> EventStream {
+        let body = OllamaChatRequest {
+            model: &request.model,
+            messages: request.messages_with_system(),
+            stream: true,
+            options: OllamaOptions::new(&request.options, self.num_ctx(&request)),
+        };
+        let builder = client.post(format!("{}/api/chat", self.base_url)).json(&body);
+
+        http::stream_ndjson(builder, "Ollama", parse_line)
+    }
+}
+
+// Context length `/api/show` reports for `model`, if it answers
+async fn context_length(client: &Client, url: &str, model: &str) -> Option<usize> {
+    let request = client.post(url).json(&serde_json::json!({ "model": model }));
+    let show: OllamaShow = http::send(request, "Ollama").await.ok()?.json().await.ok()?;
+    show.model_info
+        .iter()
+        .find(|(key, _)| key.ends_with(".context_length"))
+        .and_then(|(_, value)| value.as_u64())
+        .map(|length| length as usize)
+}
+
+fn model_info(model: OllamaModel) -> ModelInfo {
+    // Multimodal models ship a vision encoder ("clip", or "mllama" for Llama 3.2 Vision)
+    let families = model.details.families.unwrap_or_default();
+    let vision = families.iter().any(|f| f == "clip" || f == "mllama");
+
+    ModelInfo {
+        description: if model.details.parameter_size.is_empty() {
+            "Local model".to_string()
+        } else {
+            format!("Local model, {} parameters", model.details.parameter_size)
+        },
+        vision,
+        ..ModelInfo::new(model.name, "ollama")
+    }
+}
+
+fn parse_line(line: &str) -> Option<Result<StreamEvent, GhostError>> {
+    let chunk: OllamaChatChunk = serde_json::from_str(line).ok()?;
+    if let Some(error) = chunk.error {
+        return Some(Err(GhostError::ProviderError {
+            service: "Ollama".to_string(),
+            code: None,
+            message: error,
+        }));
+    }
+    if chunk.done {
+        return Some(Ok(StreamEvent::Done));
+    }
+
+    let content = chunk.message?.content;
+    if content.is_empty() {
+        return None;
+    }
+    Some(Ok(StreamEvent::Delta(content)))
+}
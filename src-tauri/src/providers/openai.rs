@@ -0,0 +1,191 @@
+// Wire format shared by OpenAI-compatible chat completion APIs (Perplexity,
+// and anything else that speaks `/chat/completions` with SSE streaming), plus
+// a provider for user-configured endpoints of that kind.
+
+use super::{fail, http, ChatMessage, ChatRequest, EventStream, ModelInfo, Provider, StreamEvent};
+use crate::error::GhostError;
+use crate::keys;
+use crate::settings::OpenAiCompatibleSettings;
+use crate::sse::SseEvent;
+use futures_util::future::{self, BoxFuture};
+use futures_util::FutureExt;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Serialize)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: ChunkDelta,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    data: Vec<ListedModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListedModel {
+    id: String,
+}
+
+pub struct OpenAiCompatibleProvider {
+    config: OpenAiCompatibleSettings,
+    // Models reported by `/models` on top of the configured ones
+    discovered: Arc<Mutex<Vec<String>>>,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(config: OpenAiCompatibleSettings) -> Self {
+        Self {
+            config,
+            discovered: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.config.base_url.trim_end_matches('/'), path)
+    }
+
+    // Attach the API key, if any, and the configured headers
+    fn authorize(&self, mut builder: RequestBuilder, api_key: Option<&str>) -> RequestBuilder {
+        if let Some(api_key) = api_key {
+            builder = builder.bearer_auth(api_key);
+        }
+        for (name, value) in &self.config.headers {
+            builder = builder.header(name, value);
+        }
+        builder
+    }
+}
+
+impl Provider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn supported_models(&self) -> Vec<String> {
+        let mut models = self.config.models.clone();
+        for model in self.discovered.lock().unwrap().iter() {
+            if !models.contains(model) {
+                models.push(model.clone());
+            }
+        }
+        models
+    }
+
+    fn discover_models(&self, client: &Client) -> BoxFuture<'static, Result<Vec<ModelInfo>, GhostError>> {
+        let api_key = match keys::stored_api_key(self.name()) {
+            Ok(api_key) => api_key,
+            Err(e) => return future::ready(Err(e)).boxed(),
+        };
+        let request = self.authorize(client.get(self.url("models")), api_key.as_deref());
+        let name = self.name().to_string();
+        let configured = self.config.models.clone();
+        let discovered = self.discovered.clone();
+
+        async move {
+            let response = http::send(request, &name).await?;
+            let list: ModelList = response
+                .json()
+                .await
+                .map_err(|e| GhostError::ProviderError {
+                    service: name.clone(),
+                    code: None,
+                    message: format!("unreadable model list ({})", e),
+                })?;
+
+            let ids: Vec<String> = list.data.into_iter().map(|m| m.id).collect();
+            *discovered.lock().unwrap() = ids.clone();
+
+            let mut models: Vec<ModelInfo> = configured.iter().map(|id| ModelInfo::new(id.as_str(), &name)).collect();
+            for id in ids {
+                if !configured.contains(&id) {
+                    models.push(ModelInfo::new(id, &name));
+                }
+            }
+            Ok(models)
+        }
+        .boxed()
+    }
+
+    fn stream(&self, client: &Client, request: ChatRequest) -> EventStream {
+        // Local servers like vLLM or LM Studio usually run without a key
+        let api_key = match keys::stored_api_key(self.name()) {
+            Ok(api_key) => api_key,
+            Err(e) => return fail(e),
+        };
+        let builder = self.authorize(client.post(self.url("chat/completions")), api_key.as_deref());
+        stream_chat_completion(builder, &request, self.name())
+    }
+}
+
+/// Stream a chat completion. `builder` is a POST to the full
+/// `/chat/completions` endpoint with authentication already attached.
+pub fn stream_chat_completion(builder: RequestBuilder, request: &ChatRequest, service: &str) -> EventStream {
+    let body = ChatCompletionRequest {
+        model: &request.model,
+        messages: request.messages_with_system(),
+        stream: true,
+        temperature: request.options.temperature,
+        top_p: request.options.top_p,
+        max_tokens: request.options.max_tokens,
+        stop: &request.options.stop,
+        seed: request.options.seed,
+    };
+
+    let name = service.to_string();
+    http::stream_sse(builder.json(&body), service, move |event| parse_event(&name, event))
+}
+
+fn parse_event(service: &str, event: &SseEvent) -> Option<Result<StreamEvent, GhostError>> {
+    if event.data == "[DONE]" {
+        return Some(Ok(StreamEvent::Done));
+    }
+
+    let value: serde_json::Value = serde_json::from_str(&event.data).ok()?;
+    if let Some(error) = value.get("error") {
+        let message = error["message"].as_str().unwrap_or("Unknown error");
+        return Some(Err(GhostError::ProviderError {
+            service: service.to_string(),
+            code: None,
+            message: message.to_string(),
+        }));
+    }
+
+    let chunk: ChatCompletionChunk = serde_json::from_value(value).ok()?;
+    let content = chunk.choices.into_iter().next()?.delta.content?;
+    if content.is_empty() {
+        return None;
+    }
+    Some(Ok(StreamEvent::Delta(content)))
+}
@@ -0,0 +1,87 @@
+use super::{fail, openai, proxy, ChatRequest, EventStream, ModelInfo, Provider};
+use crate::error::GhostError;
+use crate::generation::OptionLimits;
+use crate::keys;
+use crate::settings::{ConnectionMode, ConnectionSettings};
+use futures_util::future::{self, BoxFuture};
+use futures_util::FutureExt;
+use reqwest::Client;
+
+const API_URL: &str = "https://api.perplexity.ai/chat/completions";
+
+pub struct PerplexityProvider {
+    mode: ConnectionMode,
+    proxy_url: String,
+}
+
+impl PerplexityProvider {
+    pub fn new(connection: &ConnectionSettings) -> Self {
+        Self {
+            mode: connection.mode,
+            proxy_url: connection.proxy_url.clone(),
+        }
+    }
+}
+
+impl Provider for PerplexityProvider {
+    fn name(&self) -> &str {
+        "perplexity"
+    }
+
+    fn supported_models(&self) -> Vec<String> {
+        known_models().into_iter().map(|m| m.id).collect()
+    }
+
+    fn supports(&self, model: &str) -> bool {
+        model.starts_with("sonar")
+    }
+
+    fn option_limits(&self) -> OptionLimits {
+        OptionLimits {
+            max_temperature: 2.0,
+            max_stop_sequences: 0,
+            seed: false,
+        }
+    }
+
+    fn needs_api_key(&self) -> bool {
+        self.mode == ConnectionMode::Direct
+    }
+
+    fn discover_models(&self, client: &Client) -> BoxFuture<'static, Result<Vec<ModelInfo>, GhostError>> {
+        match self.mode {
+            ConnectionMode::Proxy => proxy::models(client, &self.proxy_url, "perplexity"),
+            ConnectionMode::Direct => future::ready(Ok(known_models())).boxed(),
+        }
+    }
+
+    fn stream(&self, client: &Client, request: ChatRequest) -> EventStream {
+        if self.mode == ConnectionMode::Direct {
+            let api_key = match keys::api_key(self.name()) {
+                Ok(api_key) => api_key,
+                Err(e) => return fail(e),
+            };
+            let builder = client.post(API_URL).bearer_auth(api_key);
+            return openai::stream_chat_completion(builder, &request, "Perplexity API");
+        }
+
+        proxy::stream(client, &self.proxy_url, "perplexity", proxy::chat_body(&request))
+    }
+}
+
+fn known_models() -> Vec<ModelInfo> {
+    vec![
+        ModelInfo {
+            name: "Sonar".to_string(),
+            description: "Fast answers with reliable search results".to_string(),
+            context_length: Some(127_072),
+            ..ModelInfo::new("sonar", "perplexity")
+        },
+        ModelInfo {
+            name: "Sonar Pro".to_string(),
+            description: "Deeper search for complex questions".to_string(),
+            context_length: Some(200_000),
+            ..ModelInfo::new("sonar-pro", "perplexity")
+        },
+    ]
+}
@@ -0,0 +1,199 @@
+// Shared transport for providers that go through the ghost-query proxy
+// server. The proxy normalises every backend to the same SSE shape:
+// `data: {"content": "..."}` chunks, `data: {"error": "...", "status": 503}` on
+// failure and a final `data: [DONE]`.
+
+use super::{http, ChatRequest, EventStream, ModelInfo, StreamEvent};
+use crate::error::GhostError;
+use crate::sse::SseEvent;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// POST `body` to `{proxy_url}/api/{endpoint}` and stream the proxy's SSE reply.
+pub fn stream(client: &Client, proxy_url: &str, endpoint: &str, body: serde_json::Value) -> EventStream {
+    let url = format!("{}/api/{}", proxy_url.trim_end_matches('/'), endpoint);
+    http::stream_sse(client.post(&url).json(&body), "proxy server", parse_event)
+}
+
+// Entry of the proxy's `/api/models` listing, grouped by provider there
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProxyModel {
+    id: String,
+    name: Option<String>,
+    #[serde(default)]
+    description: String,
+    context_length: Option<usize>,
+    #[serde(default)]
+    vision: bool,
+    #[serde(default)]
+    tools: bool,
+}
+
+/// Models the proxy offers for `provider`.
+pub fn models(
+    client: &Client,
+    proxy_url: &str,
+    provider: &'static str,
+) -> BoxFuture<'static, Result<Vec<ModelInfo>, GhostError>> {
+    let request = client.get(format!("{}/api/models", proxy_url.trim_end_matches('/')));
+
+    async move {
+        let response = http::send(request, "proxy server").await?;
+        let mut listing: HashMap<String, Vec<ProxyModel>> = response
+            .json()
+            .await
+            .map_err(|e| GhostError::ProviderError {
+                service: "proxy server".to_string(),
+                code: None,
+                message: format!("unreadable model list ({})", e),
+            })?;
+
+        let models = listing.remove(provider).unwrap_or_default();
+        Ok(models
+            .into_iter()
+            .map(|m| ModelInfo {
+                name: m.name.unwrap_or_else(|| m.id.clone()),
+                description: m.description,
+                context_length: m.context_length,
+                vision: m.vision,
+                tools: m.tools,
+                ..ModelInfo::new(m.id, provider)
+            })
+            .collect())
+    }
+    .boxed()
+}
+
+/// Body of a streaming chat request to the proxy for `request`.
+pub fn chat_body(request: &ChatRequest) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "model": request.model,
+        "messages": request.messages,
+        // Older proxy deployments only understand a single prompt
+        "prompt": request.prompt(),
+        "stream": true
+    });
+    add_options(&mut body, request);
+    body
+}
+
+// Add the system prompt and the generation options that are set. Unset ones
+// are left out so the proxy applies its defaults.
+fn add_options(body: &mut serde_json::Value, request: &ChatRequest) {
+    let options = &request.options;
+    let fields = [
+        ("system", request.system.clone().map(serde_json::Value::from)),
+        ("temperature", options.temperature.map(serde_json::Value::from)),
+        ("topP", options.top_p.map(serde_json::Value::from)),
+        ("maxTokens", options.max_tokens.map(serde_json::Value::from)),
+        ("seed", options.seed.map(serde_json::Value::from)),
+        ("stopSequences", (!options.stop.is_empty()).then(|| options.stop.clone().into())),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            body[name] = value;
+        }
+    }
+}
+
+fn parse_event(event: &SseEvent) -> Option<Result<StreamEvent, GhostError>> {
+    if event.data == "[DONE]" {
+        return Some(Ok(StreamEvent::Done));
+    }
+
+    let parsed = serde_json::from_str::<serde_json::Value>(&event.data).ok()?;
+    if let Some(content) = parsed["content"].as_str() {
+        Some(Ok(StreamEvent::Delta(content.to_string())))
+    } else {
+        parsed["error"].as_str().map(|message| Err(stream_error(message, &parsed)))
+    }
+}
+
+// The proxy answers 200 before it hears back from the backend, so a failed
+// backend request arrives in the stream, with the backend's status if the
+// proxy is new enough to send it. Map it as if the status had come directly.
+fn stream_error(message: &str, event: &serde_json::Value) -> GhostError {
+    let status = event["status"]
+        .as_u64()
+        .and_then(|status| u16::try_from(status).ok())
+        .and_then(|status| StatusCode::from_u16(status).ok());
+    let Some(status) = status else {
+        return GhostError::ProviderError {
+            service: "Proxy server".to_string(),
+            code: None,
+            message: message.to_string(),
+        };
+    };
+
+    let mut headers = HeaderMap::new();
+    if let Some(value) = event["retryAfter"].as_str().and_then(|value| HeaderValue::from_str(value).ok()) {
+        headers.insert(RETRY_AFTER, value);
+    }
+    GhostError::from_status("Proxy server", status, &headers, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::GenerationOptions;
+    use crate::providers::{ChatMessage, Role};
+    use crate::retry;
+
+    fn error_event(data: &str) -> GhostError {
+        let event = SseEvent {
+            event: "message".to_string(),
+            data: data.to_string(),
+            id: None,
+            retry: None,
+        };
+        parse_event(&event).unwrap().unwrap_err()
+    }
+
+    #[test]
+    fn backend_status_in_stream_errors_is_kept() {
+        let error = error_event(r#"{"error": "Gemini API request failed", "status": 429, "retryAfter": "12"}"#);
+        assert!(matches!(
+            error,
+            GhostError::RateLimited {
+                retry_after: Some(12),
+                ..
+            }
+        ));
+
+        assert!(retry::is_transient(&error_event(
+            r#"{"error": "Gemini API request failed", "status": 503}"#
+        )));
+        assert!(!retry::is_transient(&error_event(
+            r#"{"error": "Gemini API request failed", "status": 400}"#
+        )));
+        assert!(!retry::is_transient(&error_event(r#"{"error": "Streaming error occurred"}"#)));
+    }
+
+    #[test]
+    fn chat_body_leaves_unset_options_out() {
+        let request = ChatRequest {
+            model: "sonar".to_string(),
+            system: None,
+            messages: vec![ChatMessage {
+                role: Role::User,
+                content: "hi".to_string(),
+            }],
+            options: GenerationOptions {
+                temperature: Some(0.5),
+                ..GenerationOptions::default()
+            },
+        };
+
+        let body = chat_body(&request);
+        assert_eq!(body["prompt"], "hi");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["temperature"], 0.5);
+        assert!(body.get("system").is_none());
+        assert!(body.get("stopSequences").is_none());
+    }
+}
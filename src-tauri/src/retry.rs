@@ -0,0 +1,178 @@
+// Retries provider calls that fail before producing anything, e.g. on a 429
+// or 503 from the proxy or a connection reset. Once the stream has yielded an
+// event the error is passed on, since starting over would repeat the answer.
+
+use crate::error::GhostError;
+use crate::providers::EventStream;
+use futures_util::{stream, StreamExt};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry; it doubles with every further one.
+    pub initial_delay_ms: u64,
+    /// Longest single wait. A longer Retry-After fails the request instead.
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after `attempt` (1-based) failed with `error`, or
+    /// None to give up.
+    pub fn delay(&self, attempt: u32, error: &GhostError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !is_transient(error) {
+            return None;
+        }
+
+        if let GhostError::RateLimited {
+            retry_after: Some(seconds),
+            ..
+        } = error
+        {
+            let wait = Duration::from_secs(*seconds);
+            return (wait <= Duration::from_millis(self.max_delay_ms)).then_some(wait);
+        }
+
+        // Jitter keeps parallel requests from retrying in lockstep
+        let backoff = self.initial_delay_ms.saturating_mul(1 << (attempt - 1).min(16));
+        let ceiling = backoff.min(self.max_delay_ms);
+        Some(Duration::from_millis(rand::thread_rng().gen_range(ceiling / 2..=ceiling)))
+    }
+}
+
+/// A retry that is about to happen, reported so the UI can show progress.
+#[derive(Debug, Clone, Serialize)]
+pub struct Retry {
+    /// Number of the attempt that follows the wait.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+    /// Why the previous attempt failed.
+    pub error: GhostError,
+}
+
+/// Errors that may well not happen again on the next try.
+pub fn is_transient(error: &GhostError) -> bool {
+    match error {
+        GhostError::Network { .. } | GhostError::Timeout { .. } | GhostError::RateLimited { .. } => true,
+        // 529 is Anthropic's "overloaded"
+        GhostError::ProviderError { code: Some(code), .. } => matches!(code, 500 | 502 | 503 | 504 | 529),
+        _ => false,
+    }
+}
+
+/// Stream from `start`, starting over with a fresh stream while it fails
+/// before its first event. `on_retry` is called before each wait.
+pub fn with_retry<F, R>(policy: RetryPolicy, start: F, on_retry: R) -> EventStream
+where
+    F: Fn() -> EventStream + Send + 'static,
+    R: Fn(&Retry) + Send + 'static,
+{
+    let state = (start(), 1, false, start, on_retry);
+
+    let events = stream::unfold(state, move |(mut events, mut attempt, started, start, on_retry)| {
+        let policy = policy.clone();
+        async move {
+            loop {
+                match events.next().await? {
+                    Err(error) if !started => {
+                        let Some(delay) = policy.delay(attempt, &error) else {
+                            return Some((Err(error), (events, attempt, started, start, on_retry)));
+                        };
+                        attempt += 1;
+                        on_retry(&Retry {
+                            attempt,
+                            max_attempts: policy.max_attempts,
+                            delay_ms: delay.as_millis() as u64,
+                            error,
+                        });
+                        tokio::time::sleep(delay).await;
+                        events = start();
+                    }
+                    event => return Some((event, (events, attempt, true, start, on_retry))),
+                }
+            }
+        }
+    });
+
+    Box::pin(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::StreamEvent;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    fn unavailable() -> GhostError {
+        GhostError::ProviderError {
+            service: "Proxy server".to_string(),
+            code: Some(503),
+            message: "503 Service Unavailable".to_string(),
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay_ms: 1,
+            max_delay_ms: 1_000,
+        }
+    }
+
+    #[test]
+    fn delay_honors_retry_after_and_limits() {
+        let limited = |seconds| GhostError::RateLimited {
+            service: "Proxy server".to_string(),
+            retry_after: Some(seconds),
+        };
+        assert_eq!(policy().delay(1, &limited(1)), Some(Duration::from_secs(1)));
+        assert_eq!(policy().delay(1, &limited(60)), None);
+        assert_eq!(policy().delay(3, &unavailable()), None);
+        assert_eq!(policy().delay(1, &GhostError::InvalidModel("x".to_string())), None);
+    }
+
+    #[tokio::test]
+    async fn retries_only_before_the_first_event() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let retries = Arc::new(AtomicU32::new(0));
+
+        let counter = calls.clone();
+        let reported = retries.clone();
+        let events: Vec<_> = with_retry(
+            policy(),
+            move || {
+                // Fail the first attempt outright, then fail after some text
+                let events = match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 => vec![Err(unavailable())],
+                    _ => vec![Ok(StreamEvent::Delta("Hi".to_string())), Err(unavailable())],
+                };
+                Box::pin(stream::iter(events))
+            },
+            move |_| {
+                reported.fetch_add(1, Ordering::SeqCst);
+            },
+        )
+        .collect()
+        .await;
+
+        assert_eq!(events, vec![Ok(StreamEvent::Delta("Hi".to_string())), Err(unavailable())]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(retries.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,242 @@
+// Named conversation sessions. Each session has its own `Conversation`; the
+// list of sessions and which one is active live in `sessions.json`, and each
+// session's messages in `sessions/<id>.jsonl`.
+
+use crate::conversation::Conversation;
+use crate::error::GhostError;
+use crate::json_store;
+use crate::settings::ContextSettings;
+use crate::store::ConversationStore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Persona whose system prompt and defaults apply to this session.
+    #[serde(default)]
+    pub persona_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct SessionIndex {
+    active: String,
+    sessions: Vec<SessionInfo>,
+}
+
+pub struct SessionManager {
+    dir: Option<PathBuf>,
+    index: SessionIndex,
+    conversations: HashMap<String, Conversation>,
+    max_messages: usize,
+    summarize: bool,
+}
+
+const INDEX_FILE: &str = "sessions.json";
+
+fn session_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join("sessions").join(format!("{}.jsonl", id))
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        let mut manager = Self {
+            dir: None,
+            index: SessionIndex::default(),
+            conversations: HashMap::new(),
+            max_messages: ContextSettings::default().max_messages,
+            summarize: ContextSettings::default().summarize_dropped_turns,
+        };
+        let session = manager.insert_session("Default".to_string());
+        manager.index.active = session.id;
+        manager
+    }
+
+    /// Load sessions from `dir`, creating a default session on first run.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir.join("sessions"))?;
+
+        let mut manager = Self {
+            dir: Some(dir.to_path_buf()),
+            index: SessionIndex::default(),
+            conversations: HashMap::new(),
+            max_messages: ContextSettings::default().max_messages,
+            summarize: ContextSettings::default().summarize_dropped_turns,
+        };
+
+        manager.index = json_store::read_or_set_aside(&dir.join(INDEX_FILE))?;
+
+        if manager.index.sessions.is_empty() {
+            manager.insert_session("Default".to_string());
+        }
+
+        if manager.find(&manager.index.active).is_none() {
+            manager.index.active = manager.index.sessions[0].id.clone();
+        }
+
+        manager.save_index()?;
+        Ok(manager)
+    }
+
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions = self.index.sessions.clone();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        sessions
+    }
+
+    pub fn active_id(&self) -> &str {
+        &self.index.active
+    }
+
+    pub fn create(&mut self, name: Option<String>) -> Result<SessionInfo, GhostError> {
+        let name = name.unwrap_or_else(|| format!("Session {}", self.index.sessions.len() + 1));
+        let session = self.insert_session(name);
+        self.index.active = session.id.clone();
+        self.save_index().map_err(|e| GhostError::Storage(format!("Failed to save sessions: {}", e)))?;
+        Ok(session)
+    }
+
+    pub fn switch(&mut self, id: &str) -> Result<(), GhostError> {
+        self.require(id)?;
+        self.index.active = id.to_string();
+        self.save_index().map_err(|e| GhostError::Storage(format!("Failed to save sessions: {}", e)))
+    }
+
+    pub fn rename(&mut self, id: &str, name: String) -> Result<SessionInfo, GhostError> {
+        let session = self.find_mut(id).ok_or_else(|| GhostError::not_found("session", id))?;
+        session.name = name;
+        let session = session.clone();
+        self.save_index().map_err(|e| GhostError::Storage(format!("Failed to save sessions: {}", e)))?;
+        Ok(session)
+    }
+
+    pub fn set_persona(&mut self, id: &str, persona_id: Option<String>) -> Result<SessionInfo, GhostError> {
+        let session = self.find_mut(id).ok_or_else(|| GhostError::not_found("session", id))?;
+        session.persona_id = persona_id;
+        let session = session.clone();
+        self.save_index().map_err(|e| GhostError::Storage(format!("Failed to save sessions: {}", e)))?;
+        Ok(session)
+    }
+
+    pub fn persona_id(&self, id: &str) -> Option<String> {
+        self.find(id)?.persona_id.clone()
+    }
+
+    /// Delete a session and its history. Deleting the active session switches
+    /// to the most recently used remaining one, or a fresh session if none is left.
+    pub fn delete(&mut self, id: &str) -> Result<(), GhostError> {
+        self.require(id)?;
+        self.conversation(id)?.clear()?;
+        self.conversations.remove(id);
+        self.index.sessions.retain(|s| s.id != id);
+
+        if self.index.active == id {
+            self.index.active = match self.list().first() {
+                Some(session) => session.id.clone(),
+                None => self.insert_session("Default".to_string()).id,
+            };
+        }
+
+        self.save_index().map_err(|e| GhostError::Storage(format!("Failed to save sessions: {}", e)))
+    }
+
+    /// Conversation for session `id`, loaded from disk on first use.
+    pub fn conversation(&mut self, id: &str) -> Result<&mut Conversation, GhostError> {
+        self.require(id)?;
+
+        if !self.conversations.contains_key(id) {
+            let conversation = match &self.dir {
+                Some(dir) => Conversation::with_store(
+                    ConversationStore::new(session_path(dir, id)),
+                    self.max_messages,
+                    self.summarize,
+                ),
+                None => {
+                    let mut conversation = Conversation::new();
+                    conversation.set_limits(self.max_messages, self.summarize);
+                    conversation
+                }
+            };
+            self.conversations.insert(id.to_string(), conversation);
+        }
+
+        Ok(self.conversations.get_mut(id).unwrap())
+    }
+
+    /// Apply the history limits in `settings` to every session, loaded or not.
+    pub fn set_limits(&mut self, settings: &ContextSettings) {
+        self.max_messages = settings.max_messages;
+        self.summarize = settings.summarize_dropped_turns;
+        for conversation in self.conversations.values_mut() {
+            conversation.set_limits(self.max_messages, self.summarize);
+        }
+    }
+
+    pub fn add_message(&mut self, id: &str, role: String, content: String) -> Result<String, GhostError> {
+        let message_id = self.conversation(id)?.add_message(role, content)?;
+        self.touch(id);
+        Ok(message_id)
+    }
+
+    pub fn add_answer(&mut self, id: &str, content: String, model: String, truncated: bool) -> Result<String, GhostError> {
+        let message_id = self.conversation(id)?.add_answer(content, model, truncated)?;
+        self.touch(id);
+        Ok(message_id)
+    }
+
+    fn touch(&mut self, id: &str) {
+        if let Some(session) = self.find_mut(id) {
+            session.updated_at = now();
+        }
+        if let Err(e) = self.save_index() {
+            log::warn!("Failed to save sessions: {}", e);
+        }
+    }
+
+    fn insert_session(&mut self, name: String) -> SessionInfo {
+        let timestamp = now();
+        let session = SessionInfo {
+            id: Uuid::new_v4().to_string(),
+            name,
+            created_at: timestamp,
+            updated_at: timestamp,
+            persona_id: None,
+        };
+        self.index.sessions.push(session.clone());
+        session
+    }
+
+    fn find(&self, id: &str) -> Option<&SessionInfo> {
+        self.index.sessions.iter().find(|s| s.id == id)
+    }
+
+    fn find_mut(&mut self, id: &str) -> Option<&mut SessionInfo> {
+        self.index.sessions.iter_mut().find(|s| s.id == id)
+    }
+
+    fn require(&self, id: &str) -> Result<(), GhostError> {
+        self.find(id).map(|_| ()).ok_or_else(|| GhostError::not_found("session", id))
+    }
+
+    fn save_index(&self) -> io::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        json_store::save_json(&dir.join(INDEX_FILE), &self.index)
+    }
+}
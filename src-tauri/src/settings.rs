@@ -0,0 +1,424 @@
+// User-adjustable settings shared by the commands and background tasks,
+// persisted as `settings.json` in the app config directory. The file carries
+// a schema version; older files are migrated step by step when loaded.
+
+use crate::generation::{GenerationOptions, OptionLimits};
+use crate::hotkeys::{self, HotkeyAction};
+use crate::json_store;
+use crate::retry::RetryPolicy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Schema version written to the settings file.
+pub const VERSION: u64 = 1;
+
+const SETTINGS_FILE: &str = "settings.json";
+const DEFAULT_PROXY_URL: &str = "https://proxy-server-p9wzc2v53-prem-thatikondas-projects.vercel.app";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub context: ContextSettings,
+    pub connection: ConnectionSettings,
+    pub network: NetworkSettings,
+    /// Extra providers that speak the OpenAI chat completions API.
+    pub openai_compatible: Vec<OpenAiCompatibleSettings>,
+    pub ollama: OllamaSettings,
+    pub generation: GenerationSettings,
+    pub hotkeys: HotkeySettings,
+    /// Models to try in order when a model fails before answering, keyed by
+    /// the model asked for, e.g. "gemini-2.0-flash" => ["sonar", "llama3.2"].
+    pub fallbacks: BTreeMap<String, Vec<String>>,
+}
+
+/// HTTP client setup, for slow networks and corporate proxies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    pub connect_timeout_secs: u64,
+    /// How long a connection may go without receiving any data.
+    pub idle_timeout_secs: u64,
+    /// How long a streamed answer may go without a new chunk. Unlike the idle
+    /// timeout, keep-alives don't count.
+    pub stall_timeout_secs: u64,
+    /// Proxy for all requests, e.g. "http://proxy.corp:8080" or
+    /// "socks5://localhost:1080". Without one, HTTP(S)_PROXY is used if set.
+    pub proxy: Option<String>,
+    /// PEM or DER files with CA certificates to trust in addition to the
+    /// system's, e.g. for a TLS-inspecting corporate proxy.
+    pub ca_certificates: Vec<PathBuf>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            idle_timeout_secs: 90,
+            stall_timeout_secs: 120,
+            proxy: None,
+            ca_certificates: Vec::new(),
+        }
+    }
+}
+
+/// How requests reach the model providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionMode {
+    /// Through the ghost-query proxy server, which holds the API keys.
+    #[default]
+    Proxy,
+    /// Straight to each provider's API with locally stored keys, so prompts
+    /// never pass through a third-party server.
+    Direct,
+}
+
+/// Where API keys for direct mode are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStorage {
+    /// Passphrase-encrypted file in the app data directory.
+    #[default]
+    Vault,
+    /// The operating system's credential store.
+    Keyring,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    pub mode: ConnectionMode,
+    pub key_storage: KeyStorage,
+    /// Fall back to `<PROVIDER>_API_KEY` environment variables for providers
+    /// without a stored key. Off unless turned on explicitly.
+    pub env_api_keys: bool,
+    /// Base URL of the ghost-query proxy server used in proxy mode.
+    pub proxy_url: String,
+    /// Retries for requests that fail before the answer starts.
+    pub retry: RetryPolicy,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        Self {
+            mode: ConnectionMode::default(),
+            key_storage: KeyStorage::default(),
+            env_api_keys: false,
+            proxy_url: DEFAULT_PROXY_URL.to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// An OpenAI-compatible endpoint such as OpenAI itself, Groq, Together, vLLM or
+/// LM Studio. These are always called directly, whatever the connection mode.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct OpenAiCompatibleSettings {
+    /// Provider name, also used to look up its API key.
+    pub name: String,
+    /// API root including the version, e.g. "https://api.openai.com/v1".
+    pub base_url: String,
+    /// Models served by this endpoint, in addition to those it lists under `/models`.
+    pub models: Vec<String>,
+    /// Extra headers sent with every request.
+    pub headers: BTreeMap<String, String>,
+}
+
+/// A local Ollama server. Its models are discovered at runtime.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OllamaSettings {
+    pub enabled: bool,
+    pub base_url: String,
+}
+
+impl Default for OllamaSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_url: "http://localhost:11434".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeySettings {
+    /// Global shortcuts, e.g. "Ctrl+Alt+G", and the action each one triggers.
+    pub bindings: BTreeMap<String, HotkeyAction>,
+    /// Presses closer together than this are ignored, so a bouncing key
+    /// doesn't toggle the window twice.
+    pub debounce_ms: u64,
+}
+
+impl Default for HotkeySettings {
+    fn default() -> Self {
+        Self {
+            bindings: BTreeMap::from([("Ctrl+Shift+Space".to_string(), HotkeyAction::ToggleWindow)]),
+            debounce_ms: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationSettings {
+    /// Used for every model.
+    pub defaults: GenerationOptions,
+    /// Per-model overrides of `defaults`, keyed by model ID.
+    pub models: BTreeMap<String, GenerationOptions>,
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        Self {
+            defaults: GenerationOptions {
+                temperature: Some(0.7),
+                max_tokens: Some(2048),
+                ..GenerationOptions::default()
+            },
+            models: BTreeMap::new(),
+        }
+    }
+}
+
+impl GenerationSettings {
+    /// Defaults for `model`, before any options given with the request.
+    pub fn options_for(&self, model: &str) -> GenerationOptions {
+        match self.models.get(model) {
+            Some(options) => self.defaults.merge(options),
+            None => self.defaults.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextSettings {
+    /// Most tokens of conversation history sent with a request.
+    pub token_budget: usize,
+    /// Tokens kept free in the model's context window for the answer.
+    pub reserve_output_tokens: usize,
+    /// Fold turns that no longer fit the budget into a running summary
+    /// instead of dropping them.
+    pub summarize_dropped_turns: bool,
+    /// Model that writes the summaries; a cheap, fast one is best.
+    pub summary_model: String,
+    /// Messages per session kept in memory; the token budget decides what is sent.
+    pub max_messages: usize,
+}
+
+impl Default for ContextSettings {
+    fn default() -> Self {
+        Self {
+            token_budget: 16_000,
+            reserve_output_tokens: 2048,
+            summarize_dropped_turns: true,
+            summary_model: "gemini-2.0-flash".to_string(),
+            max_messages: 200,
+        }
+    }
+}
+
+impl ContextSettings {
+    /// History budget for a model with `context_window` tokens: the configured
+    /// budget, capped so that the history plus the output reserve fits.
+    pub fn budget_for(&self, context_window: usize) -> usize {
+        let available = context_window.saturating_sub(self.reserve_output_tokens);
+        self.token_budget.min(available)
+    }
+}
+
+impl Settings {
+    /// Load the settings from `dir`, migrating older files. Without a file,
+    /// the defaults are used, picking up `PROXY_URL` from the environment as
+    /// earlier versions did. A file that can't be used is moved to
+    /// `settings.json.corrupt`, so saving new settings doesn't overwrite it.
+    pub fn load(dir: &Path) -> Result<Settings, String> {
+        let path = dir.join(SETTINGS_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return parse(json!({})),
+            Err(e) => return Err(format!("Failed to read settings: {}", e)),
+        };
+
+        let value = serde_json::from_str(&contents).map_err(|e| format!("Invalid settings file: {}", e));
+        value.and_then(parse).map_err(|e| match json_store::set_aside(&path) {
+            Ok(aside) => format!("{}; moved it to {}", e, aside.display()),
+            Err(rename) => format!("{}; failed to move it aside: {}", e, rename),
+        })
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let write = || -> io::Result<()> {
+            let mut value = serde_json::to_value(self)?;
+            value["version"] = VERSION.into();
+            fs::create_dir_all(dir)?;
+            json_store::save_json(&dir.join(SETTINGS_FILE), &value)
+        };
+        write().map_err(|e| format!("Failed to save settings: {}", e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.context.token_budget == 0 {
+            return Err("Context token budget must be greater than zero".to_string());
+        }
+        if self.context.reserve_output_tokens == 0 {
+            return Err("Reserved output tokens must be greater than zero".to_string());
+        }
+        if self.context.max_messages == 0 {
+            return Err("Messages kept in memory must be greater than zero".to_string());
+        }
+        if !is_http_url(&self.connection.proxy_url) {
+            return Err("Proxy URL must start with http:// or https://".to_string());
+        }
+        let network = &self.network;
+        if network.connect_timeout_secs == 0 || network.idle_timeout_secs == 0 || network.stall_timeout_secs == 0 {
+            return Err("Network timeouts must be greater than zero".to_string());
+        }
+        if let Some(proxy) = network.proxy.as_deref().filter(|proxy| !proxy.trim().is_empty()) {
+            let schemes = ["http://", "https://", "socks5://", "socks5h://"];
+            if !schemes.iter().any(|scheme| proxy.starts_with(scheme)) {
+                return Err("Network proxy must start with http://, https://, socks5:// or socks5h://".to_string());
+            }
+        }
+
+        let retry = &self.connection.retry;
+        if !(1..=10).contains(&retry.max_attempts) {
+            return Err("Request attempts must be between 1 and 10".to_string());
+        }
+        if retry.initial_delay_ms > retry.max_delay_ms {
+            return Err("Initial retry delay must not exceed the maximum delay".to_string());
+        }
+
+        hotkeys::parse_bindings(&self.hotkeys.bindings).map_err(|e| e.to_string())?;
+
+        // Provider-specific ranges are checked per request
+        let limits = OptionLimits::default();
+        self.generation.defaults.validate(&limits, "the defaults")?;
+        for (model, options) in &self.generation.models {
+            options.validate(&limits, model)?;
+        }
+
+        for (model, chain) in &self.fallbacks {
+            for (i, fallback) in chain.iter().enumerate() {
+                if fallback.trim().is_empty() {
+                    return Err(format!("Fallbacks for {} must not be empty", model));
+                }
+                if fallback == model || chain[..i].contains(fallback) {
+                    return Err(format!("{} appears more than once in the fallbacks for {}", fallback, model));
+                }
+            }
+        }
+
+        if !is_http_url(&self.ollama.base_url) {
+            return Err("Ollama URL must start with http:// or https://".to_string());
+        }
+
+        let mut names = vec!["gemini", "perplexity", "anthropic", "ollama"];
+        for provider in &self.openai_compatible {
+            if provider.name.trim().is_empty() {
+                return Err("Provider name must not be empty".to_string());
+            }
+            if names.contains(&provider.name.as_str()) {
+                return Err(format!("Provider name '{}' is already in use", provider.name));
+            }
+            names.push(&provider.name);
+
+            if !is_http_url(&provider.base_url) {
+                return Err(format!("Base URL of '{}' must start with http:// or https://", provider.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Bring a settings file of any earlier schema version up to `VERSION`.
+// Migrate and check the contents of a settings file
+fn parse(value: Value) -> Result<Settings, String> {
+    let settings: Settings =
+        serde_json::from_value(migrate(value)?).map_err(|e| format!("Invalid settings file: {}", e))?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+fn migrate(mut value: Value) -> Result<Value, String> {
+    if !value.is_object() {
+        return Err("Invalid settings file: expected an object".to_string());
+    }
+
+    let mut version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > VERSION {
+        return Err(format!(
+            "Settings were written by a newer version of the app (schema {}, this app understands {})",
+            version, VERSION
+        ));
+    }
+
+    while version < VERSION {
+        match version {
+            0 => migrate_v0(&mut value),
+            _ => unreachable!("no migration from settings version {}", version),
+        }
+        version += 1;
+    }
+
+    value["version"] = version.into();
+    Ok(value)
+}
+
+// Version 0 predates the settings file, when the proxy URL came from the
+// environment
+fn migrate_v0(value: &mut Value) {
+    if value["connection"].get("proxy_url").is_none() {
+        if let Ok(url) = env::var("PROXY_URL") {
+            if !value["connection"].is_object() {
+                value["connection"] = json!({});
+            }
+            value["connection"]["proxy_url"] = url.into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_current_version() {
+        let value = migrate(json!({})).unwrap();
+        assert_eq!(value["version"], json!(VERSION));
+        assert!(serde_json::from_value::<Settings>(value).is_ok());
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(migrate(json!({ "version": VERSION + 1 })).is_err());
+    }
+
+    #[test]
+    fn unusable_file_is_set_aside() {
+        let dir = std::env::temp_dir().join(format!("ghost-query-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let invalid = json!({ "version": VERSION, "context": { "token_budget": 0 } }).to_string();
+        fs::write(dir.join(SETTINGS_FILE), &invalid).unwrap();
+
+        assert!(Settings::load(&dir).is_err());
+        assert!(!dir.join(SETTINGS_FILE).exists());
+        assert_eq!(fs::read_to_string(dir.join("settings.json.corrupt")).unwrap(), invalid);
+        assert!(Settings::load(&dir).is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+// Parser for `text/event-stream` bodies, following the WHATWG server-sent
+// events spec. Splitting the body into lines is left to `lines`.
+
+use crate::lines::LineParser;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SseEvent {
+    /// Value of the `event:` field, "message" if none was sent.
+    pub event: String,
+    /// All `data:` lines of the event joined with '\n'.
+    pub data: String,
+    /// Last `id:` seen on the stream, if any.
+    pub id: Option<String>,
+    /// Reconnection time in milliseconds from a `retry:` field in this event.
+    pub retry: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct SseParser {
+    started: bool,
+    event: String,
+    data: String,
+    last_event_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl LineParser for SseParser {
+    type Item = SseEvent;
+
+    fn parse_line(&mut self, line: &str) -> Option<SseEvent> {
+        let line = if self.started {
+            line
+        } else {
+            self.started = true;
+            line.strip_prefix('\u{FEFF}').unwrap_or(line)
+        };
+
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        // Comment, typically used as a keep-alive
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok();
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    // Servers that close the connection without a trailing blank line still
+    // get their last event delivered
+    fn finish(&mut self) -> Option<SseEvent> {
+        self.dispatch()
+    }
+}
+
+impl SseParser {
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.event);
+        let retry = self.retry.take();
+
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let mut data = std::mem::take(&mut self.data);
+        data.pop(); // trailing '\n' added after the last data line
+
+        Some(SseEvent {
+            event: if event.is_empty() { "message".to_string() } else { event },
+            data,
+            id: self.last_event_id.clone(),
+            retry,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::{self, LineDecoder};
+    use futures_util::{stream, StreamExt};
+
+    // Feed `input` split at every given offset and collect all events
+    fn decode_split(input: &[u8], splits: &[usize]) -> Vec<SseEvent> {
+        let mut decoder = LineDecoder::new(SseParser::default());
+        let mut events = Vec::new();
+        let mut last = 0;
+        for &split in splits {
+            events.extend(decoder.feed(&input[last..split]));
+            last = split;
+        }
+        events.extend(decoder.feed(&input[last..]));
+        events.extend(decoder.finish());
+        events
+    }
+
+    // Feed `input` one byte at a time
+    fn decode_bytewise(input: &[u8]) -> Vec<SseEvent> {
+        let splits: Vec<usize> = (1..input.len()).collect();
+        decode_split(input, &splits)
+    }
+
+    fn data(events: &[SseEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.data.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_simple_events() {
+        let events = decode_split(b"data: hello\n\ndata: world\n\n", &[]);
+        assert_eq!(data(&events), vec!["hello", "world"]);
+        assert_eq!(events[0].event, "message");
+    }
+
+    #[test]
+    fn data_line_split_across_chunks() {
+        let input = b"data: {\"content\":\"hello\"}\n\ndata: [DONE]\n\n";
+        for split in 1..input.len() {
+            let events = decode_split(input, &[split]);
+            assert_eq!(data(&events), vec!["{\"content\":\"hello\"}", "[DONE]"], "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn multibyte_utf8_split_across_chunks() {
+        let input = "data: héllo → 世界 🎉\n\n".as_bytes();
+        assert_eq!(data(&decode_bytewise(input)), vec!["héllo → 世界 🎉"]);
+    }
+
+    #[test]
+    fn crlf_split_between_cr_and_lf() {
+        let input = b"data: one\r\n\r\ndata: two\r\n\r\n";
+        let cr = input.iter().position(|&b| b == b'\r').unwrap();
+        let events = decode_split(input, &[cr + 1]);
+        assert_eq!(data(&events), vec!["one", "two"]);
+        assert_eq!(data(&decode_bytewise(input)), vec!["one", "two"]);
+    }
+
+    #[test]
+    fn bare_cr_line_endings() {
+        assert_eq!(data(&decode_bytewise(b"data: a\r\rdata: b\r\r")), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn multiline_data_is_joined() {
+        let events = decode_bytewise(b"data: first\ndata: second\ndata:\ndata: fourth\n\n");
+        assert_eq!(data(&events), vec!["first\nsecond\n\nfourth"]);
+    }
+
+    #[test]
+    fn event_id_and_retry_fields() {
+        let input = b"event: content_block_delta\nid: 42\nretry: 3000\ndata: x\n\ndata: y\n\n";
+        let events = decode_bytewise(input);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "content_block_delta");
+        assert_eq!(events[0].id.as_deref(), Some("42"));
+        assert_eq!(events[0].retry, Some(3000));
+        // Event type and retry reset after dispatch, the last event ID persists
+        assert_eq!(events[1].event, "message");
+        assert_eq!(events[1].id.as_deref(), Some("42"));
+        assert_eq!(events[1].retry, None);
+    }
+
+    #[test]
+    fn invalid_retry_is_ignored() {
+        let events = decode_bytewise(b"retry: soon\ndata: x\n\n");
+        assert_eq!(events[0].retry, None);
+    }
+
+    #[test]
+    fn comments_and_empty_events_are_skipped() {
+        let input = b": keep-alive\n\nevent: ping\n\n:another comment\ndata: real\n\n";
+        let events = decode_bytewise(input);
+        assert_eq!(data(&events), vec!["real"]);
+        assert_eq!(events[0].event, "message");
+    }
+
+    #[test]
+    fn field_without_colon_and_without_space() {
+        let events = decode_bytewise(b"data\ndata:tight\n\n");
+        assert_eq!(data(&events), vec!["\ntight"]);
+    }
+
+    #[test]
+    fn leading_bom_is_stripped() {
+        let input = "\u{FEFF}data: bom\n\n".as_bytes();
+        assert_eq!(data(&decode_bytewise(input)), vec!["bom"]);
+    }
+
+    #[test]
+    fn unterminated_final_event_is_flushed() {
+        assert_eq!(data(&decode_bytewise(b"data: one\n\ndata: tail")), vec!["one", "tail"]);
+        assert_eq!(data(&decode_bytewise(b"data: tail\r")), vec!["tail"]);
+    }
+
+    #[tokio::test]
+    async fn decode_stream_of_fragments() {
+        let chunks: Vec<Result<Vec<u8>, ()>> = vec![
+            Ok(b"da".to_vec()),
+            Ok(b"ta: {\"content\":\"caf\xc3".to_vec()),
+            Ok(b"\xa9\"}\r".to_vec()),
+            Ok(b"\n\r\ndata: [DONE]".to_vec()),
+        ];
+
+        let events: Vec<SseEvent> = lines::decode(stream::iter(chunks), SseParser::default())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(data(&events), vec!["{\"content\":\"café\"}", "[DONE]"]);
+    }
+}
@@ -0,0 +1,69 @@
+// On-disk conversation log. Each session's messages are appended to their own
+// JSONL file in the app data directory so history survives restarts.
+
+use crate::conversation::{ConversationMessage, Summary};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub struct ConversationStore {
+    path: PathBuf,
+}
+
+impl ConversationStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Every stored message, oldest first. Lines that fail to parse (e.g. a
+    /// write cut short by a crash) are skipped.
+    pub fn load(&self) -> io::Result<Vec<ConversationMessage>> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut messages = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if let Ok(message) = serde_json::from_str(&line) {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+
+    pub fn append(&self, message: &ConversationMessage) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(message)?)
+    }
+
+    pub fn load_summary(&self) -> io::Result<Option<Summary>> {
+        match fs::read_to_string(self.summary_path()) {
+            Ok(contents) => Ok(serde_json::from_str(&contents).ok()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save_summary(&self, summary: &Summary) -> io::Result<()> {
+        fs::write(self.summary_path(), serde_json::to_string(summary)?)
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        for path in [self.path.clone(), self.summary_path()] {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Lives next to the log, e.g. `<session>.summary.json`
+    fn summary_path(&self) -> PathBuf {
+        self.path.with_extension("summary.json")
+    }
+}
@@ -0,0 +1,78 @@
+// Bookkeeping for generations that are currently streaming. Every
+// `ask_ai_stream` call gets its own ID and cancellation token so several
+// requests can run side by side and be stopped individually.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct StreamHandle {
+    pub token: CancellationToken,
+    // Whether a stopped answer is kept in the conversation (marked truncated) or dropped
+    keep_partial: Arc<AtomicBool>,
+}
+
+impl StreamHandle {
+    pub fn keep_partial(&self) -> bool {
+        self.keep_partial.load(Ordering::Relaxed)
+    }
+
+    fn cancel(&self, keep_partial: bool) {
+        self.keep_partial.store(keep_partial, Ordering::Relaxed);
+        self.token.cancel();
+    }
+}
+
+pub struct ActiveStreams {
+    streams: Mutex<HashMap<String, StreamHandle>>,
+}
+
+impl ActiveStreams {
+    pub fn new() -> Self {
+        Self {
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register a new request under `id`, or a fresh ID if none is given, and
+    /// return its ID and handle. None if a request with `id` is already running.
+    pub fn start(&self, id: Option<String>) -> Option<(String, StreamHandle)> {
+        let id = id.unwrap_or_else(|| Uuid::new_v4().to_string());
+        let mut streams = self.streams.lock().unwrap();
+        if streams.contains_key(&id) {
+            return None;
+        }
+
+        let handle = StreamHandle {
+            token: CancellationToken::new(),
+            keep_partial: Arc::new(AtomicBool::new(true)),
+        };
+        streams.insert(id.clone(), handle.clone());
+        Some((id, handle))
+    }
+
+    /// Cancel one request. Returns false if it isn't running.
+    pub fn cancel(&self, id: &str, keep_partial: bool) -> bool {
+        match self.streams.lock().unwrap().get(id) {
+            Some(handle) => {
+                handle.cancel(keep_partial);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cancel_all(&self, keep_partial: bool) {
+        for handle in self.streams.lock().unwrap().values() {
+            handle.cancel(keep_partial);
+        }
+    }
+
+    /// Forget a request once it has finished, failed or been cancelled.
+    pub fn finish(&self, id: &str) {
+        self.streams.lock().unwrap().remove(id);
+    }
+}
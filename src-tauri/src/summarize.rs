@@ -0,0 +1,58 @@
+// Folds turns that fell out of the context window into a running summary,
+// written by a (cheap) model through the normal provider path.
+
+use crate::conversation::SummaryJob;
+use crate::error::GhostError;
+use crate::generation::GenerationOptions;
+use crate::providers::{ChatMessage, ChatRequest, Provider, Role, StreamEvent};
+use futures_util::StreamExt;
+use reqwest::Client;
+
+const INSTRUCTIONS: &str = "Summarize the conversation below so the summary can replace it as context for \
+future replies. Keep facts, decisions, names, numbers, code identifiers and open questions; drop pleasantries. \
+Write at most 200 words and reply with the summary only.";
+
+pub async fn summarize(provider: &dyn Provider, client: &Client, model: String, job: &SummaryJob) -> Result<String, GhostError> {
+    let mut prompt = INSTRUCTIONS.to_string();
+
+    if let Some(previous) = &job.previous {
+        prompt.push_str("\n\nSummary so far:\n");
+        prompt.push_str(previous);
+    }
+
+    prompt.push_str("\n\nConversation:\n");
+    for message in &job.messages {
+        prompt.push_str(&format!("{}: {}\n", message.role, message.content));
+    }
+
+    let request = ChatRequest {
+        model,
+        system: None,
+        messages: vec![ChatMessage {
+            role: Role::User,
+            content: prompt,
+        }],
+        options: GenerationOptions::default(),
+    };
+
+    let mut stream = provider.stream(client, request);
+    let mut summary = String::new();
+
+    while let Some(event) = stream.next().await {
+        match event? {
+            StreamEvent::Delta(content) => summary.push_str(&content),
+            StreamEvent::Done => break,
+            StreamEvent::Stop { .. } => {}
+        }
+    }
+
+    let summary = summary.trim();
+    if summary.is_empty() {
+        return Err(GhostError::ProviderError {
+            service: provider.name().to_string(),
+            code: None,
+            message: "Model returned an empty summary".to_string(),
+        });
+    }
+    Ok(summary.to_string())
+}
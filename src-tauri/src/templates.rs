@@ -0,0 +1,132 @@
+// Reusable prompt templates with `{{variable}}` placeholders, kept in
+// `templates.json`. Values come from the request; `ask_ai_stream` fills in the
+// built-ins `{{input}}` (the typed text), `{{clipboard}}`, `{{selection}}` and
+// `{{date}}` when the request doesn't.
+
+use crate::json_store::{JsonStore, Record};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    pub body: String,
+}
+
+pub type TemplateManager = JsonStore<Template>;
+
+impl Template {
+    pub fn new(name: String, body: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            body,
+        }
+    }
+}
+
+impl Record for Template {
+    const KIND: &'static str = "template";
+    const FILE: &'static str = "templates.json";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Template name must not be empty".to_string());
+        }
+        if self.body.trim().is_empty() {
+            return Err("Template must not be empty".to_string());
+        }
+        variables(&self.body).map(|_| ())
+    }
+}
+
+/// Names of the variables used in `body`, in order of first use.
+pub fn variables(body: &str) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::new();
+    for part in parse(body)? {
+        if let Part::Variable(name) = part {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Replace every `{{name}}` in `body` with its value. Fails if a variable has no value.
+pub fn render(body: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let mut rendered = String::with_capacity(body.len());
+    for part in parse(body)? {
+        match part {
+            Part::Text(text) => rendered.push_str(text),
+            Part::Variable(name) => {
+                let value = values
+                    .get(name)
+                    .ok_or_else(|| format!("No value for template variable {{{{{}}}}}", name))?;
+                rendered.push_str(value);
+            }
+        }
+    }
+    Ok(rendered)
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Variable(&'a str),
+}
+
+fn parse(body: &str) -> Result<Vec<Part<'_>>, String> {
+    let mut parts = Vec::new();
+    let mut rest = body;
+
+    while let Some(start) = rest.find("{{") {
+        parts.push(Part::Text(&rest[..start]));
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "Template has an unclosed {{".to_string())?;
+
+        let name = after[..end].trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("Invalid template variable name: {{{{{}}}}}", &after[..end]));
+        }
+        parts.push(Part::Variable(name));
+        rest = &after[end + 2..];
+    }
+
+    parts.push(Part::Text(rest));
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_variables() {
+        let values = HashMap::from([
+            ("input".to_string(), "cannot borrow".to_string()),
+            ("lang".to_string(), "Rust".to_string()),
+        ]);
+        let rendered = render("Explain this {{ lang }} error:\n{{input}}\n({{lang}})", &values).unwrap();
+        assert_eq!(rendered, "Explain this Rust error:\ncannot borrow\n(Rust)");
+    }
+
+    #[test]
+    fn lists_variables_once() {
+        assert_eq!(variables("{{a}} {{b}} {{ a }}").unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn rejects_missing_values_and_bad_syntax() {
+        assert!(render("Hi {{name}}", &HashMap::new()).is_err());
+        assert!(variables("Hi {{name").is_err());
+        assert!(variables("Hi {{first name}}").is_err());
+    }
+}
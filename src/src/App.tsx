@@ -16,11 +16,64 @@ interface ConversationMessage {
   role: string;
   content: string;
   timestamp: number;
+  truncated?: boolean;
+  model?: string;
+}
+
+interface StreamPayload {
+  request_id: string;
+  content: string;
+}
+
+// Mirrors GhostError on the Rust side
+interface GhostError {
+  kind: string;
+  message: string;
+  service?: string;
+  retry_after?: number | null;
+  code?: number | null;
+}
+
+interface StreamErrorPayload {
+  request_id: string;
+  error: GhostError;
+}
+
+interface StreamFallbackPayload {
+  request_id: string;
+  from: string;
+  to: string;
+  error: GhostError;
+}
+
+interface StreamRetryPayload {
+  request_id: string;
+  attempt: number;
+  max_attempts: number;
+  delay_ms: number;
+  error: GhostError;
+}
+
+function errorMessage(error: unknown): string {
+  if (typeof error === "object" && error !== null && "message" in error) {
+    const { kind, message, retry_after } = error as GhostError;
+    if (kind === "rate_limited" && retry_after) {
+      return `${message}. Try again in ${retry_after}s.`;
+    }
+    return message;
+  }
+  return String(error);
+}
+
+interface HotkeyActionPayload {
+  action: string;
+  prompt: string | null;
 }
 
 function App() {
   const [message, setMessage] = useState("");
   const [isLoading, setIsLoading] = useState(false);
+  const [retryStatus, setRetryStatus] = useState("");
   const [response, setResponse] = useState("");
   const [error, setError] = useState("");
   const [conversationHistory, setConversationHistory] = useState<
@@ -34,29 +87,64 @@ function App() {
   const [fullResponseCopied, setFullResponseCopied] = useState(false);
   const responseRef = useRef<HTMLDivElement>(null);
   const inputRef = useRef<HTMLTextAreaElement>(null);
+  const requestIdRef = useRef<string | null>(null);
 
   // Set up event listeners for streaming responses
   useEffect(() => {
-    const unlistenChunk = listen<string>("ai-response-chunk", (event) => {
-      setResponse((prev) => prev + event.payload);
-    });
+    // Ignore events from requests other than the one currently shown
+    const isStale = (requestId: string) =>
+      requestIdRef.current !== null && requestId !== requestIdRef.current;
 
-    const unlistenDone = listen<string>("ai-response-done", (_event) => {
+    const unlistenChunk = listen<StreamPayload>(
+      "ai-response-chunk",
+      (event) => {
+        if (isStale(event.payload.request_id)) return;
+        setResponse((prev) => prev + event.payload.content);
+      }
+    );
+
+    const unlistenDone = listen<StreamPayload>("ai-response-done", (event) => {
+      if (isStale(event.payload.request_id)) return;
       setIsLoading(false);
       setMessage("");
       // Refresh conversation history after response is complete
       loadConversationHistory();
     });
 
-    const unlistenError = listen<string>("ai-response-error", (event) => {
-      setError(event.payload);
-      setIsLoading(false);
-      console.error("AI request failed:", event.payload);
-    });
+    const unlistenError = listen<StreamErrorPayload>(
+      "ai-response-error",
+      (event) => {
+        if (isStale(event.payload.request_id)) return;
+        setError(errorMessage(event.payload.error));
+        setIsLoading(false);
+        console.error("AI request failed:", event.payload.error);
+      }
+    );
 
-    const unlistenCancelled = listen<string>(
+    const unlistenRetry = listen<StreamRetryPayload>(
+      "ai-response-retry",
+      (event) => {
+        if (isStale(event.payload.request_id)) return;
+        const { attempt, max_attempts, error } = event.payload;
+        setRetryStatus(
+          `${errorMessage(error)}. Retrying (attempt ${attempt} of ${max_attempts})...`
+        );
+      }
+    );
+
+    const unlistenFallback = listen<StreamFallbackPayload>(
+      "ai-response-fallback",
+      (event) => {
+        if (isStale(event.payload.request_id)) return;
+        const { from, to, error } = event.payload;
+        setRetryStatus(`${errorMessage(error)}. Asking ${to} instead of ${from}...`);
+      }
+    );
+
+    const unlistenCancelled = listen<StreamPayload>(
       "ai-response-cancelled",
       (event) => {
+        if (isStale(event.payload.request_id)) return;
         setIsLoading(false);
         console.log("Stream cancelled:", event.payload);
       }
@@ -66,6 +154,8 @@ function App() {
       unlistenChunk.then((unlisten) => unlisten());
       unlistenDone.then((unlisten) => unlisten());
       unlistenError.then((unlisten) => unlisten());
+      unlistenRetry.then((unlisten) => unlisten());
+      unlistenFallback.then((unlisten) => unlisten());
       unlistenCancelled.then((unlisten) => unlisten());
     };
   }, []);
@@ -82,6 +172,17 @@ function App() {
     loadConversationHistory();
   }, []);
 
+  // Show what went wrong while the app started, e.g. an unusable proxy setting
+  useEffect(() => {
+    invoke<GhostError[]>("take_startup_errors")
+      .then((errors) => {
+        if (errors.length > 0) {
+          setError(errors.map(errorMessage).join("\n"));
+        }
+      })
+      .catch((err) => console.error("Failed to load startup errors:", err));
+  }, []);
+
   const loadConversationHistory = async () => {
     try {
       const history = await invoke<ConversationMessage[]>(
@@ -111,29 +212,72 @@ function App() {
     }
   }, [response]);
 
-  const handleSubmit = async () => {
-    if (message.trim()) {
-      console.log("Message:", message);
+  const sendPrompt = async (prompt: string) => {
+    if (prompt.trim()) {
+      console.log("Message:", prompt);
       setIsLoading(true);
       setError("");
+      setRetryStatus("");
       setResponse("");
 
+      // Pick the ID here so events that arrive before invoke returns aren't
+      // taken for the previous request's
+      const requestId = crypto.randomUUID();
+      requestIdRef.current = requestId;
+
       try {
-        await invoke("ask_ai_stream", {
-          prompt: message,
+        await invoke<string>("ask_ai_stream", {
+          prompt,
           model: selectedModel,
+          requestId,
         });
       } catch (err) {
-        setError(err as string);
+        setError(errorMessage(err));
         setIsLoading(false);
         console.error("AI request failed:", err);
       }
     }
   };
 
+  const handleSubmit = () => sendPrompt(message);
+
+  // Hotkey listeners are set up once, so they reach the latest sendPrompt through a ref
+  const sendPromptRef = useRef(sendPrompt);
+  sendPromptRef.current = sendPrompt;
+
+  useEffect(() => {
+    const unlistenAction = listen<HotkeyActionPayload>(
+      "hotkey-action",
+      (event) => {
+        const { action, prompt } = event.payload;
+        if (prompt) {
+          setMessage(prompt);
+          sendPromptRef.current(prompt);
+        } else if (action === "new_session") {
+          setResponse("");
+          setError("");
+          loadConversationHistory();
+        }
+      }
+    );
+
+    const unlistenError = listen<{ error: GhostError }>("hotkey-error", (event) => {
+      setError(errorMessage(event.payload.error));
+    });
+
+    return () => {
+      unlistenAction.then((unlisten) => unlisten());
+      unlistenError.then((unlisten) => unlisten());
+    };
+  }, []);
+
   const handleStop = async () => {
     try {
-      await invoke("stop_streaming");
+      if (requestIdRef.current) {
+        await invoke("cancel_request", { requestId: requestIdRef.current });
+      } else {
+        await invoke("stop_streaming");
+      }
       setIsLoading(false);
     } catch (err) {
       console.error("Failed to stop streaming:", err);
@@ -372,7 +516,9 @@ function App() {
                   }`}
                 >
                   <div className="font-medium text-xs mb-1 flex justify-between items-center">
-                    <span>{msg.role === "user" ? "You" : "AI"}</span>
+                    <span>
+                      {msg.role === "user" ? "You" : msg.model ?? "AI"}
+                    </span>
                     <Button
                       size="sm"
                       variant="ghost"
@@ -410,7 +556,9 @@ function App() {
                 {formatResponse(response)}
               </ReactMarkdown>
               {isLoading && !response && (
-                <span className="text-muted-foreground">Thinking...</span>
+                <span className="text-muted-foreground">
+                  {retryStatus || "Thinking..."}
+                </span>
               )}
               {/* Copy Full Response Button - positioned at end of content */}
               {response && (
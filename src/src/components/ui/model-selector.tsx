@@ -1,6 +1,7 @@
 "use client";
 
-import { useState } from "react";
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { Button } from "./button";
 import { ChevronDown, Bot } from "lucide-react";
 import { cn } from "../../lib/utils";
@@ -10,6 +11,9 @@ export interface ModelOption {
   name: string;
   provider: string;
   description: string;
+  context_length?: number | null;
+  vision?: boolean;
+  tools?: boolean;
 }
 
 interface ModelSelectorProps {
@@ -18,40 +22,36 @@ interface ModelSelectorProps {
   className?: string;
 }
 
-const MODELS: ModelOption[] = [
-  {
-    id: "gemini-2.0-flash",
-    name: "Gemini 2.0 Flash",
-    provider: "Google",
-    description: "Advanced Gemini model for fast responses",
-  },
-  // {
-  //   id: "gemini-1.5-pro",
-  //   name: "Gemini 1.5 Pro",
-  //   provider: "Google",
-  //   description: "Most capable model for complex tasks",
-  // },
-  // {
-  //   id: "sonar",
-  //   name: "Perplexity Sonar",
-  //   provider: "Perplexity",
-  //   description: "Fast answers with reliable search results",
-  // },
-];
-
 export function ModelSelector({
   selectedModel,
   onModelChange,
   className,
 }: ModelSelectorProps) {
   const [isOpen, setIsOpen] = useState(false);
+  const [models, setModels] = useState<ModelOption[]>([]);
+
+  // The backend aggregates and caches models from every configured provider
+  useEffect(() => {
+    invoke<ModelOption[]>("list_models")
+      .then((available) => {
+        setModels(available);
+        if (
+          available.length > 0 &&
+          !available.some((model) => model.id === selectedModel)
+        ) {
+          onModelChange(available[0].id);
+        }
+      })
+      .catch((err) => console.error("Failed to load models:", err));
+  }, []);
 
-  const selectedModelData =
-    MODELS.find((model) => model.id === selectedModel) || MODELS[0];
+  const selectedModelData = models.find(
+    (model) => model.id === selectedModel
+  ) || { id: selectedModel, name: selectedModel, provider: "", description: "" };
 
   return (
     <>
-      {MODELS.length === 1 ? (
+      {models.length <= 1 ? (
         <div className={cn("flex items-center gap-2", className)}>
           <Bot className="h-4 w-4" />
           <span className="text-sm font-medium">{selectedModelData.name}</span>
@@ -81,7 +81,7 @@ export function ModelSelector({
           {isOpen && (
             <div className="absolute top-full left-0 right-0 mt-1 bg-background border rounded-md shadow-lg z-50">
               <div className="p-1">
-                {MODELS.map((model) => (
+                {models.map((model) => (
                   <button
                     key={model.id}
                     onClick={() => {